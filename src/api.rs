@@ -0,0 +1,54 @@
+use axum::{extract::State, http::{header, HeaderMap, StatusCode}, response::IntoResponse, routing::{get, post}, Json, Router};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::{mpsc::UnboundedSender, oneshot};
+use crate::{game::Level, networking::Event};
+
+// Questions for the game loop, answered over the oneshot sent alongside them
+pub enum ApiRequest {
+  Status,
+  Players,
+  Metrics, // Answered with the exposition text as a json string
+  Load { level: String, secret: Option<String> },
+}
+pub type ApiResponse = (StatusCode, Value);
+
+#[derive(Deserialize)]
+struct LoadRequest {
+  level: String,
+}
+
+pub fn routes() -> Router<UnboundedSender<Event>> {
+  Router::new()
+    .route("/api/status", get(|State(tx): State<UnboundedSender<Event>>| ask(tx, ApiRequest::Status)))
+    .route("/api/players", get(|State(tx): State<UnboundedSender<Event>>| ask(tx, ApiRequest::Players)))
+    .route("/metrics", get(|State(tx): State<UnboundedSender<Event>>| async move {
+      let (status, body) = ask(tx, ApiRequest::Metrics).await;
+      match body.0 {
+        Value::String(text) => (status, [(header::CONTENT_TYPE, "text/plain; version=0.0.4")], text).into_response(),
+        body => (status, Json(body)).into_response(),
+      }
+    }))
+    .route("/api/levels", get(|| async { Json(json!(Level::available())) }))
+    .route("/api/level", post(
+      |State(tx): State<UnboundedSender<Event>>, headers: HeaderMap, Json(request): Json<LoadRequest>| {
+        // Same secret as the websocket admin login, sent as "Authorization: Bearer <secret>"
+        let secret = headers.get("authorization")
+          .and_then(|value| value.to_str().ok())
+          .and_then(|value| value.strip_prefix("Bearer "))
+          .map(str::to_owned);
+        ask(tx, ApiRequest::Load { level: request.level, secret })
+      }
+    ))
+}
+
+async fn ask(tx: UnboundedSender<Event>, request: ApiRequest) -> (StatusCode, Json<Value>) {
+  let (response_tx, response) = oneshot::channel();
+  if tx.send(Event::Api(request, response_tx)).is_err() {
+    return (StatusCode::SERVICE_UNAVAILABLE, Json(json!({ "error": "game loop stopped" })));
+  }
+  match response.await {
+    Ok((status, body)) => (status, Json(body)),
+    Err(_) => (StatusCode::SERVICE_UNAVAILABLE, Json(json!({ "error": "game loop stopped" }))),
+  }
+}
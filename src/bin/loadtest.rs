@@ -0,0 +1,232 @@
+// Connects a crowd of bot mice to a running server and reports how it holds up
+// cargo run --release --bin loadtest -- --bots 50 --rate 60 --seconds 30 --movement circle
+use std::{collections::HashMap, sync::Arc, time::{Duration, Instant}};
+use futures::{SinkExt, StreamExt};
+use glam::IVec2;
+use parking_lot::Mutex;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use mouse_game::Broadcast;
+use mouse_game::protocol::{encode_movement, ClientMessage, ServerMessage, COMPACT_VERSION, PROTOCOL_VERSION};
+
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Movement {
+  Random,
+  Circle,
+}
+
+struct Options {
+  url: String,
+  bots: usize,
+  rate: u32, // Movement messages per second, per bot
+  seconds: u64,
+  movement: Movement,
+}
+impl Options {
+  fn parse() -> Result<Self, String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut options = Self { url: "ws://127.0.0.1:8080/ws".to_owned(), bots: 10, rate: 60, seconds: 10, movement: Movement::Random };
+    for pair in args.chunks(2) {
+      let [flag, value] = pair else { return Err(format!("{} is missing a value", pair[0])) };
+      let number = || value.parse().map_err(|_| format!("{flag} takes a number"));
+      match flag.as_str() {
+        "--url" => options.url = value.clone(),
+        "--bots" => options.bots = number()? as usize,
+        "--rate" => options.rate = (number()? as u32).max(1),
+        "--seconds" => options.seconds = number()?,
+        "--movement" => options.movement = match value.as_str() {
+          "random" => Movement::Random,
+          "circle" => Movement::Circle,
+          _ => return Err("movement is random or circle".to_owned()),
+        },
+        _ => return Err(format!("unknown flag {flag}")),
+      }
+    }
+    Ok(options)
+  }
+}
+
+#[derive(Default)]
+struct Stats {
+  connected: usize,
+  players: usize, // The rest were made to spectate
+  dropped: usize,
+  failed: usize,
+  bytes: u64,
+  messages: u64,
+  bad_messages: u64,
+  latencies: Vec<Duration>, // From a probe being sent to it showing up in a broadcast
+}
+
+// Deterministic per bot, so runs are comparable
+struct XorShift(u64);
+impl XorShift {
+  fn next(&mut self) -> i32 {
+    self.0 ^= self.0 << 13;
+    self.0 ^= self.0 >> 7;
+    self.0 ^= self.0 << 17;
+    (self.0 % 41) as i32 - 20
+  }
+}
+
+fn movement(kind: Movement, random: &mut XorShift, step: u32) -> IVec2 {
+  match kind {
+    Movement::Random => IVec2::new(random.next(), random.next()),
+    Movement::Circle => {
+      let angle = step as f32 / 30.0;
+      (glam::Vec2::from_angle(angle) * 20.0).round().as_ivec2()
+    }
+  }
+}
+
+async fn run_bot(index: usize, options: Arc<Options>, stats: Arc<Mutex<Stats>>, deadline: Instant) {
+  let url = format!("{}?version={PROTOCOL_VERSION}", options.url);
+  let Ok((socket, _)) = connect_async(url.as_str()).await else {
+    stats.lock().failed += 1;
+    return
+  };
+  stats.lock().connected += 1;
+  let (mut sender, mut receiver) = socket.split();
+  let join = ClientMessage::Join { name: format!("bot{index}"), color: None };
+  let _ = sender.send(Message::Text(serde_json::to_string(&join).unwrap().into())).await;
+
+  let mut random = XorShift((index as u64).wrapping_mul(0x9e3779b97f4a7c15) + 1);
+  let mut mover = tokio::time::interval(Duration::from_secs(1) / options.rate);
+  let mut prober = tokio::time::interval(PROBE_INTERVAL);
+  // Pings are a round trip through the game loop that we can spot in the broadcasts
+  let mut probes: HashMap<IVec2, Instant> = HashMap::new();
+  let mut probe_count = 0;
+  let mut bounds = None; // Of the level, from its last full state
+  let mut playing = false;
+  let mut version = 0; // What the server agreed to talk, from its hello
+  let mut step = 0;
+  let finished = tokio::time::sleep_until(deadline.into());
+  tokio::pin!(finished);
+  loop {
+    tokio::select! {
+      _ = &mut finished => {
+        let _ = sender.send(Message::Close(None)).await;
+        return
+      }
+      _ = mover.tick(), if playing => {
+        step += 1;
+        let delta = movement(options.movement, &mut random, step);
+        if sender.send(Message::Binary(encode_movement(delta).into())).await.is_err() { break }
+      }
+      _ = prober.tick(), if playing && bounds.is_some() => {
+        probe_count += 1;
+        // Spread over the level, the server moves pings placed outside of it
+        let (min, max): (IVec2, IVec2) = bounds.unwrap();
+        let span = (max - min).max(IVec2::ONE);
+        let spot = index as i32 * 7919 + probe_count;
+        let center = min + IVec2::new(spot.rem_euclid(span.x), (spot / span.x).rem_euclid(span.y));
+        let ping = ClientMessage::Ping { position: Some(center) };
+        if sender.send(Message::Text(serde_json::to_string(&ping).unwrap().into())).await.is_err() { break }
+        // Ping markers are 12x12 and positioned by their top left
+        probes.insert(center - IVec2::splat(6), Instant::now());
+      }
+      message = receiver.next() => {
+        let Some(Ok(message)) = message else { break };
+        let mut stats = stats.lock();
+        stats.messages += 1;
+        match message {
+          Message::Binary(bytes) => {
+            stats.bytes += bytes.len() as u64;
+            let broadcast = if version >= COMPACT_VERSION { Broadcast::decode_compact(&bytes) } else { Broadcast::decode_bytes(&bytes) };
+            match broadcast {
+              Ok(Broadcast::State { clear, updates }) => {
+                // The first state after connecting has everything too, without clearing
+                if clear || bounds.is_none() {
+                  // Walls and the like, mice and pings (materials 0 and 9) can be anywhere
+                  bounds = updates.iter()
+                    .filter(|(_, update)| update.material.is_some_and(|material| material != 0 && material != 9))
+                    .filter_map(|(_, update)| update.position)
+                    .fold(None, |bounds, position| match bounds {
+                      None => Some((position, position)),
+                      Some((min, max)) => Some((position.min(min), position.max(max))),
+                    });
+                }
+                for (_, update) in updates {
+                  let Some(sent) = update.position.and_then(|position| probes.remove(&position)) else { continue };
+                  stats.latencies.push(sent.elapsed());
+                }
+              }
+              Ok(_) => (),
+              Err(_) => stats.bad_messages += 1,
+            }
+          }
+          Message::Text(text) => {
+            stats.bytes += text.len() as u64;
+            match serde_json::from_str(&text) {
+              Ok(ServerMessage::Hello { version: agreed, .. }) => version = agreed,
+              Ok(ServerMessage::Role { spectating }) => {
+                if !spectating && !playing { stats.players += 1; }
+                if spectating && playing { stats.players -= 1; }
+                playing = !spectating;
+              }
+              Ok(ServerMessage::Rejected { reason }) => println!("bot{index} rejected: {reason}"),
+              _ => (),
+            }
+          }
+          _ => (),
+        }
+      }
+    }
+  }
+  let mut stats = stats.lock();
+  if playing { stats.players -= 1; }
+  if Instant::now() < deadline { stats.dropped += 1; }
+}
+
+fn percentile(sorted: &[Duration], fraction: f64) -> Duration {
+  if sorted.is_empty() { return Duration::ZERO }
+  sorted[((sorted.len() - 1) as f64 * fraction).round() as usize]
+}
+
+fn report(stats: &mut Stats, elapsed: Duration, last_bytes: &mut u64) {
+  stats.latencies.sort();
+  println!(
+    "{:>4}s  bots {:>4} ({} playing)  dropped {}  failed {}  received {:>8} KiB (+{} KiB/s)  messages {}  bad {}  latency p50 {:?} p99 {:?} max {:?}",
+    elapsed.as_secs(),
+    stats.connected - stats.dropped,
+    stats.players,
+    stats.dropped,
+    stats.failed,
+    stats.bytes / 1024,
+    (stats.bytes - *last_bytes) / 1024,
+    stats.messages,
+    stats.bad_messages,
+    percentile(&stats.latencies, 0.5),
+    percentile(&stats.latencies, 0.99),
+    stats.latencies.last().copied().unwrap_or_default(),
+  );
+  *last_bytes = stats.bytes;
+}
+
+#[tokio::main]
+async fn main() {
+  let options = match Options::parse() {
+    Ok(options) => Arc::new(options),
+    Err(error) => {
+      println!("{error}\nusage: loadtest [--url ws://127.0.0.1:8080/ws] [--bots 10] [--rate 60] [--seconds 10] [--movement random|circle]");
+      return
+    }
+  };
+  let stats = Arc::new(Mutex::new(Stats::default()));
+  let started = Instant::now();
+  let deadline = started + Duration::from_secs(options.seconds);
+  let bots: Vec<_> = (0..options.bots)
+    .map(|index| tokio::spawn(run_bot(index, options.clone(), stats.clone(), deadline)))
+    .collect();
+
+  let mut last_bytes = 0;
+  let mut reporter = tokio::time::interval_at((started + Duration::from_secs(1)).into(), Duration::from_secs(1));
+  while Instant::now() < deadline {
+    reporter.tick().await;
+    report(&mut stats.lock(), started.elapsed(), &mut last_bytes);
+  }
+  for bot in bots { let _ = bot.await; }
+  println!("Finished");
+  report(&mut stats.lock(), started.elapsed(), &mut last_bytes);
+}
@@ -0,0 +1,59 @@
+use std::{io::BufRead, time::Duration};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
+use crate::networking::Event;
+
+// What levels, animations and the physics step were tuned for
+pub const DEFAULT_TICK_RATE: u32 = 50;
+pub const MIN_TICK_RATE: u32 = 1;
+pub const MAX_TICK_RATE: u32 = 240;
+// Wrong secrets a connection may try within LOGIN_WINDOW, so it can't be guessed
+pub const LOGIN_ATTEMPTS: usize = 3;
+pub const LOGIN_WINDOW: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum Command {
+  Restart,
+  Load(String),
+  Kick(String), // Player name, mouse id or connection id
+  Freeze(bool),
+  TickRate(u32),
+  LogLevel(String), // tracing filter directives, ie. "debug" or "info,mouse_game=trace"
+  Bots(bool), // Fill in for missing players
+}
+impl Command {
+  // Same syntax for the server console and admins typing "/command" in chat
+  pub fn parse(line: &str) -> Result<Self, String> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let argument = words.collect::<Vec<_>>().join(" ");
+    match (command, argument.as_str()) {
+      ("restart", "") => Ok(Self::Restart),
+      ("load", level) if !level.is_empty() => Ok(Self::Load(level.to_owned())),
+      ("kick", player) if !player.is_empty() => Ok(Self::Kick(player.to_owned())),
+      ("freeze", "") => Ok(Self::Freeze(true)),
+      ("unfreeze", "") => Ok(Self::Freeze(false)),
+      ("bots", "on") => Ok(Self::Bots(true)),
+      ("bots", "off") => Ok(Self::Bots(false)),
+      ("loglevel", directives) if !directives.is_empty() => Ok(Self::LogLevel(directives.to_owned())),
+      ("tickrate", rate) => rate.parse::<u32>()
+        .map(|rate| Self::TickRate(rate.clamp(MIN_TICK_RATE, MAX_TICK_RATE)))
+        .map_err(|_| "usage: tickrate <ticks per second>".to_owned()),
+      _ => Err("commands: restart, load <level>, kick <name|mouse id>, freeze, unfreeze, tickrate <n>, bots on|off, loglevel <filter>".to_owned()),
+    }
+  }
+}
+
+// Forwards commands typed into the server's terminal to the game loop
+pub fn spawn_console(server_tx: UnboundedSender<Event>) {
+  std::thread::spawn(move || {
+    for line in std::io::stdin().lock().lines() {
+      let Ok(line) = line else { break };
+      if line.trim().is_empty() { continue }
+      match Command::parse(&line) {
+        Ok(command) => if server_tx.send(Event::Command(command)).is_err() { break },
+        Err(usage) => println!("{usage}"),
+      }
+    }
+  });
+}
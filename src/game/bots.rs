@@ -0,0 +1,169 @@
+use std::collections::VecDeque;
+use glam::IVec2;
+use super::{Level, Material};
+
+// Width of a navigation cell in world units
+const CELL: i32 = 10;
+// Roughly the mouse's box, measured from its top left
+const MOUSE_SIZE: IVec2 = IVec2::new(11, 18);
+// Extra room kept between a mouse and anything it shouldn't touch
+const CLEARANCE: i32 = 3;
+// Close enough to count as standing on the target
+const REACH: i32 = 4;
+// Largest movement a bot sends in one tick
+const MAX_PUSH: i32 = 400;
+// Walls can hide and mice get shoved around, so paths don't last forever
+pub const REPLAN_TICKS: u64 = 50;
+pub const BOT_NAME: &str = "Bot";
+pub const BOT_COLOR: u32 = 0x909090;
+
+// Movement toward a point, easing off as it gets close
+pub fn steer(offset: IVec2) -> IVec2 {
+  (offset * 10).clamp(IVec2::splat(-MAX_PUSH), IVec2::splat(MAX_PUSH))
+}
+
+pub fn arrived(offset: IVec2) -> bool { offset.abs().max_element() <= REACH }
+
+pub struct Bot {
+  pub object_id: usize,
+  pub slot: usize, // Index into the level's button slots
+  pub path: VecDeque<IVec2>, // Waypoints, the last one being the slot itself
+  pub planned: bool,
+}
+impl Bot {
+  pub fn new(object_id: usize, slot: usize) -> Self {
+    Self { object_id, slot, path: VecDeque::new(), planned: false }
+  }
+
+  // Skips waypoints it's already passed, None once there's nowhere left to go
+  pub fn next_move(&mut self, position: IVec2) -> Option<IVec2> {
+    while self.path.len() > 1 && (self.path[0] - position).abs().max_element() <= CELL {
+      self.path.pop_front();
+    }
+    let offset = *self.path.front()? - position;
+    if self.path.len() > 1 {
+      // Full speed until the last waypoint, they're too close together to ease into
+      return Some((offset.as_vec2().normalize_or_zero() * MAX_PUSH as f32).as_ivec2())
+    }
+    if arrived(offset) {
+      self.path.clear();
+      return None
+    }
+    Some(steer(offset))
+  }
+}
+
+// Where bots should stand, each channel's requirement spread across its buttons
+pub fn button_slots(level: &Level) -> Vec<IVec2> {
+  let mut slots = Vec::new();
+  for channel in &level.channels {
+    let buttons: Vec<(IVec2, IVec2)> = level.objects().into_iter()
+      .filter(|(_, object)| matches!(object.material, Material::Button(button_channel, _) if button_channel as u8 % 16 == *channel))
+      .map(|(_, object)| object.bounds())
+      .collect();
+    if buttons.is_empty() { continue }
+    let required = level.button_requirements[*channel as usize] as usize;
+    for button in 0..buttons.len() {
+      // Side by side along the button so bots don't shove each other off it
+      let sharing = (button..required).step_by(buttons.len()).count() as i32;
+      let (min, max) = buttons[button];
+      for spot in 0..sharing {
+        let x = min.x + (max.x - min.x) * (spot + 1) / (sharing + 1) - MOUSE_SIZE.x / 2;
+        let y = (min.y + max.y) / 2 - MOUSE_SIZE.y / 2;
+        slots.push(IVec2::new(x, y));
+      }
+    }
+  }
+  slots
+}
+
+// Which cells a mouse's top left can sit in without touching walls or hazards
+pub struct NavGrid {
+  origin: IVec2,
+  size: IVec2,
+  blocked: Vec<bool>,
+}
+impl NavGrid {
+  pub fn new(level: &Level) -> Self {
+    let mut obstacles = Vec::new();
+    for (id, object) in level.objects() {
+      // Moving things are dodged by replanning, not by the grid
+      if object.hidden || level.animated.contains(&id) || level.dynamic.contains(&id) { continue }
+      if matches!(object.material, Material::Wall | Material::PinkWall | Material::Death | Material::BigDeath) {
+        obstacles.push(object.bounds());
+      }
+    }
+    let (min, max) = level.bounds();
+    let origin = min - MOUSE_SIZE - IVec2::splat(CELL);
+    let size = (max - origin) / CELL + 2;
+    let mut blocked = vec![false; (size.x * size.y) as usize];
+    for (index, blocked) in blocked.iter_mut().enumerate() {
+      let top_left = origin + IVec2::new(index as i32 % size.x, index as i32 / size.x) * CELL;
+      let bottom_right = top_left + MOUSE_SIZE;
+      *blocked = obstacles.iter().any(|(min, max)| {
+        top_left.x - CLEARANCE < max.x && bottom_right.x + CLEARANCE > min.x &&
+        top_left.y - CLEARANCE < max.y && bottom_right.y + CLEARANCE > min.y
+      });
+    }
+    Self { origin, size, blocked }
+  }
+
+  fn cell(&self, position: IVec2) -> IVec2 {
+    ((position - self.origin + CELL / 2).div_euclid(IVec2::splat(CELL))).clamp(IVec2::ZERO, self.size - 1)
+  }
+
+  fn index(&self, cell: IVec2) -> usize { (cell.y * self.size.x + cell.x) as usize }
+
+  // The open cell closest to the point where a mouse wouldn't overlap any of the others
+  pub fn free_spot(&self, near: IVec2, mice: &[IVec2]) -> IVec2 {
+    let start = self.cell(near);
+    let mut seen = vec![false; self.blocked.len()];
+    seen[self.index(start)] = true;
+    let mut frontier = VecDeque::from([start]);
+    while let Some(cell) = frontier.pop_front() {
+      let spot = self.origin + cell * CELL;
+      let crowded = mice.iter().any(|mouse| (*mouse - spot).abs().cmplt(MOUSE_SIZE + CLEARANCE).all());
+      if !self.blocked[self.index(cell)] && !crowded { return spot }
+      for next in self.neighbours(cell) {
+        let index = self.index(next);
+        if seen[index] { continue }
+        seen[index] = true;
+        frontier.push_back(next);
+      }
+    }
+    near
+  }
+
+  fn neighbours(&self, cell: IVec2) -> impl Iterator<Item = IVec2> {
+    let size = self.size;
+    [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y].into_iter()
+      .map(move |step| cell + step)
+      .filter(move |next| next.cmpge(IVec2::ZERO).all() && next.cmplt(size).all())
+  }
+
+  // Breadth first over open cells, None when walled off from the target
+  pub fn path(&self, from: IVec2, to: IVec2) -> Option<VecDeque<IVec2>> {
+    let (start, goal) = (self.cell(from), self.cell(to));
+    let mut came_from = vec![None; self.blocked.len()];
+    came_from[self.index(start)] = Some(start);
+    let mut frontier = VecDeque::from([start]);
+    while let Some(cell) = frontier.pop_front() {
+      if cell == goal { break }
+      for next in self.neighbours(cell) {
+        let index = self.index(next);
+        // The goal itself may brush a wall, the button is what matters
+        if came_from[index].is_some() || (self.blocked[index] && next != goal) { continue }
+        came_from[index] = Some(cell);
+        frontier.push_back(next);
+      }
+    }
+    came_from[self.index(goal)]?;
+    let mut path = VecDeque::from([to]);
+    let mut cell = goal;
+    while cell != start {
+      cell = came_from[self.index(cell)].unwrap();
+      if cell != start { path.push_front(self.origin + cell * CELL); }
+    }
+    Some(path)
+  }
+}
@@ -0,0 +1,39 @@
+use std::{collections::VecDeque, time::Duration};
+use uuid::Uuid;
+use super::ServerMessage;
+use super::rate_limit::RateLimit;
+
+const MAX_LENGTH: usize = 200;
+const HISTORY_LENGTH: usize = 50;
+// At most RATE_LIMIT messages per connection within RATE_WINDOW, so nobody floods the chat
+const RATE_LIMIT: usize = 5;
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+pub struct Chat {
+  history: VecDeque<ServerMessage>,
+  recent: RateLimit,
+}
+impl Chat {
+  pub fn new() -> Self {
+    Self { history: VecDeque::new(), recent: RateLimit::new(RATE_LIMIT, RATE_WINDOW) }
+  }
+
+  // The message to relay, if the text is valid and the sender isn't spamming
+  pub fn accept(&mut self, id: Uuid, name: String, color: Option<u32>, text: &str, now: Duration) -> Option<ServerMessage> {
+    let text: String = text.trim().chars()
+      .filter(|char| !char.is_control())
+      .take(MAX_LENGTH).collect();
+    if text.is_empty() { return None }
+    if self.recent.limited(id, now) { return None }
+    self.recent.hit(id, now);
+
+    let message = ServerMessage::Chat { name, color, text };
+    if self.history.len() == HISTORY_LENGTH { self.history.pop_front(); }
+    self.history.push_back(message.clone());
+    Some(message)
+  }
+
+  pub fn history(&self) -> impl Iterator<Item = &ServerMessage> { self.history.iter() }
+
+  pub fn forget(&mut self, id: Uuid) { self.recent.forget(id); }
+}
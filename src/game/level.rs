@@ -1,11 +1,12 @@
 use rapier2d::{na::Vector2, prelude::*};
 use glam::IVec2;
-use std::collections::{HashMap, HashSet};
+use std::{collections::{HashMap, HashSet}, time::Duration};
 use lilypads::Pond;
 use parking_lot::Mutex;
-use crate::game::Material;
+use crate::game::{Material, object::MAX_TEAMS};
 use serde::Deserialize;
-use super::{Object, Physics, serde::InitialLevel};
+use tracing::{debug, info, warn};
+use super::{Object, Physics, serde::{Condition, InitialLevel, Motor, Requirement}};
 use super::state::ObjectUpdate;
 
 #[derive(Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -22,37 +23,108 @@ struct RemoteControl {
 
 pub struct Level {
   objects: Pond<Object>,
-  pub list: HashMap<usize, RigidBodyHandle>,
-  pub players: HashSet<usize>,
-  pub animated: HashSet<usize>,
+  pub(crate) list: HashMap<usize, RigidBodyHandle>,
+  pub(crate) players: HashSet<usize>,
+  pub(crate) bots: HashSet<usize>, // Players that fill in on buttons, but don't need to reach exits
+  pub(crate) animated: HashSet<usize>,
+  pub(crate) dynamic: HashSet<usize>,
   next: Option<String>,
   current: String,
   
   events: Mutex<Vec<CollisionEvent>>,
   players_on_button: HashMap<usize, usize>, // id, channel
-  button_requirements: [u8; 16], // Arbitrarily support 16 channels
+  requirements: [Requirement; 16], // Arbitrarily support 16 channels
+  pub(crate) button_requirements: [u8; 16], // Effective for the current player count
+  pub(crate) channels_held: [u8; 16],
+  pub(crate) channels: Vec<u8>, // Channels the level actually uses
+  pub(crate) status_changed: bool,
+  teams: u8,
+  collected: HashMap<usize, u32>, // player id, items collected
+  pub(crate) total_collected: u32,
+  players_in_exit: HashMap<usize, u32>, // id, exits overlapped
+  win: Condition,
+  fail: Option<Condition>,
+  elapsed: Duration, // Game time since the level started
+  time_limit: Option<u32>, // Seconds, also part of the fail condition
+  won: bool,
+  pub(crate) deaths: u64, // Drained into the game's stats every tick
+  pub(crate) wins: u64,
   receivers: HashSet<RemoteControl>,
+  motors: Vec<JointMotor>,
+}
+
+struct JointMotor {
+  handle: ImpulseJointHandle,
+  axis: JointAxis,
+  motor: Motor,
+  active: Option<bool>,
 }
 impl Level {
-  pub fn tick(&mut self, physics: &mut Physics, state_changes: &mut HashMap<usize, ObjectUpdate>) -> Option<String> {
+  pub fn name(&self) -> &str { &self.current }
+
+  #[tracing::instrument(level = "debug", skip_all)]
+  pub(crate) fn tick(&mut self, physics: &mut Physics, state_changes: &mut HashMap<usize, ObjectUpdate>, tick_length: Duration) -> Option<String> {
     for event in self.events.get_mut().clone() {
       if self.handle_event(event, physics, state_changes) {
         return Some(self.current.clone());
       }
     }
-    let win = self.handle_remote(physics, state_changes);
-    if win { return self.next.clone();}
+    let time_left = self.time_left();
+    self.elapsed += tick_length;
+    // So the HUD counts down a second at a time
+    if self.time_left() != time_left { self.status_changed = true; }
+    match self.handle_remote(physics, state_changes) {
+      Some(true) => {
+        // Levels without a next one stay won, only count it once
+        if !self.won {
+          info!(level = self.current, next = self.next, "level won");
+          self.wins += 1;
+        }
+        self.won = true;
+        if self.next.is_some() { return self.next.clone() }
+      }
+      Some(false) => {
+        info!(level = self.current, "level failed");
+        return Some(self.current.clone())
+      }
+      None => (),
+    }
     self.events.get_mut().clear();
     self.register_movement(physics.body_sets().0, state_changes);
     None
   }
   
-  fn handle_remote(&mut self, physics: &mut Physics, state_changes: &mut HashMap<usize, ObjectUpdate>) -> bool {
+  // Some(won) once the level is over
+  fn handle_remote(&mut self, physics: &mut Physics, state_changes: &mut HashMap<usize, ObjectUpdate>) -> Option<bool> {
     let mut channels_held = [0; 16];
-    for channel in self.players_on_button.values() { channels_held[*channel as usize] += 1; }
-    // Win channel
-    if channels_held[0] >= self.button_requirements[0] { return true; }
+    for channel in self.players_on_button.values() { channels_held[*channel] += 1; }
+    if channels_held != self.channels_held {
+      for (channel, &held) in channels_held.iter().enumerate() {
+        let required = self.button_requirements[channel];
+        if (held >= required) != (self.channels_held[channel] >= required) {
+          info!(channel, held, required, active = held >= required, "channel changed");
+        }
+      }
+      self.channels_held = channels_held;
+      self.status_changed = true;
+    }
+    let seconds = self.elapsed.as_secs_f32();
+    if self.condition_met(&self.win, &channels_held, seconds) { return Some(true); }
+    // A last level that's been won stays won, even once its time runs out
+    if !self.won && self.fail.as_ref().is_some_and(|fail| self.condition_met(fail, &channels_held, seconds)) { return Some(false); }
     
+    for joint in self.motors.iter_mut() {
+      let activate = joint.motor.channel.is_none_or(|channel|
+        channels_held[channel as usize] >= self.button_requirements[channel as usize]
+      );
+      if joint.active == Some(activate) { continue }
+      joint.active = Some(activate);
+      let speed = if activate { joint.motor.speed } else { joint.motor.idle_speed };
+      if let Some(data) = physics.joint_mut(joint.handle) {
+        data.set_motor_velocity(joint.axis, speed, joint.motor.factor);
+      }
+    }
+
     for receiver in self.receivers.iter() {
       let activate = channels_held[receiver.channel as usize] >= self.button_requirements[receiver.channel as usize];
       let obj = self.objects.get_mut(receiver.id).unwrap();
@@ -74,7 +146,27 @@ impl Level {
         }
       }
     }
-    false
+    None
+  }
+
+  fn condition_met(&self, condition: &Condition, channels_held: &[u8; 16], seconds: f32) -> bool {
+    match condition {
+      Condition::Channel(channel) => {
+        let channel = *channel as usize % 16;
+        channels_held[channel] >= self.button_requirements[channel]
+      }
+      Condition::AllInExit => {
+        let mut humans = self.players.difference(&self.bots).peekable();
+        humans.peek().is_some() && humans.all(|id| self.players_in_exit.contains_key(id))
+      }
+      Condition::Collected(required) => self.total_collected >= *required,
+      Condition::Survive(limit) => seconds >= *limit as f32,
+      Condition::All(conditions) => conditions.iter()
+        .all(|condition| self.condition_met(condition, channels_held, seconds)),
+      Condition::Any(conditions) => conditions.iter()
+        .any(|condition| self.condition_met(condition, channels_held, seconds)),
+      Condition::Not(condition) => !self.condition_met(condition, channels_held, seconds),
+    }
   }
 
   fn handle_event(&mut self, event: CollisionEvent, physics: &mut Physics, state_changes: &mut HashMap<usize, ObjectUpdate>) -> bool {
@@ -83,48 +175,105 @@ impl Level {
       CollisionEvent::Started(h1, h2, _) => (true, h1, h2),
       CollisionEvent::Stopped(h1, h2, _) => (false, h1, h2),
     };
-    let id_1 = id_from_collider(handle_1, &rigids, &colliders);
-    let id_2 = id_from_collider(handle_2, &rigids, &colliders);
+    let id_1 = id_from_collider(handle_1, rigids, colliders);
+    let id_2 = id_from_collider(handle_2, rigids, colliders);
+    // Dynamic objects can now set off sensors too, so only count actual players
+    let is_player = |id: Option<usize>| id.is_some_and(|id|
+      matches!(self.objects.get(id).unwrap().material, Material::Player)
+    );
+    let has_event = |id: Option<usize>| id.is_some_and(|id|
+      self.objects.get(id).unwrap().material.has_event()
+    );
+    // ie. a dynamic wall that overlapped an item when the item was deleted
+    if ![id_1, id_2].into_iter().any(|id| is_player(id) || has_event(id)) { return false }
     let (player_id_maybe, sensor_id) =
-      if id_1.is_none() { (id_1, id_2.unwrap()) } else if id_2.is_none() { (id_2, id_1.unwrap()) } 
-      else if matches!(
-        self.objects.get(id_1.unwrap()).unwrap().material, Material::Player
-      ) { (id_1, id_2.unwrap()) } else { (id_2, id_1.unwrap()) };
+      if is_player(id_1) { (id_1, id_2) }
+      else if is_player(id_2) { (id_2, id_1) }
+      else if has_event(id_1) { (None, id_1) }
+      else { (None, id_2.or(id_1)) };
+    let other_id = if sensor_id == id_1 { id_2 } else { id_1 };
+    // The sensor was deleted earlier this tick (ie. an already collected item)
+    let Some(sensor_id) = sensor_id else { return false };
     let sensor_type = self.objects.get(sensor_id).unwrap().material;
     match sensor_type {
       Material::BigDeath => {
-        return true
+        if let Some(player_id) = player_id_maybe && started {
+          info!(player = player_id, level = self.current, "player hit big death, restarting");
+          self.deaths += 1;
+          return true
+        }
       }
       Material::Death => {
-        if let Some(player_id) = player_id_maybe {
-          let Material::Player = self.objects.get(player_id).unwrap().material else { unreachable!() };
+        if let Some(player_id) = player_id_maybe && started {
+          info!(player = player_id, "player died");
+          self.deaths += 1;
           self.set_rapier_pos(player_id, rigids, IVec2::ZERO);
         }
       }
       Material::Button(channel, _) => {
+        // Only players press buttons down (not crates or doors). Anything already removed
+        // was a player leaving, whose channel delete() let go of but who still lit the button
+        if player_id_maybe.is_none() && other_id.is_some() { return false }
         let button= self.objects.get_mut(sensor_id).unwrap();
         button.material.set_active(started);
         state_changes.entry(sensor_id)
           .or_insert(ObjectUpdate::new()).material(button.material);
         if let Some(player_id) = player_id_maybe {
           if started {
-            self.players_on_button.insert(player_id, channel as usize);
+            self.players_on_button.insert(player_id, channel as usize % 16);
           } else {
             self.players_on_button.remove(&player_id);
           }
         }
       }
+      Material::Collectible => {
+        if let Some(player_id) = player_id_maybe && started {
+          debug!(player = player_id, item = sensor_id, "collected");
+          *self.collected.entry(player_id).or_insert(0) += 1;
+          self.total_collected += 1;
+          self.status_changed = true;
+          self.delete(sensor_id, physics);
+          state_changes.entry(sensor_id)
+            .or_insert(ObjectUpdate::new()).delete();
+        }
+      }
+      Material::Exit => {
+        if let Some(player_id) = player_id_maybe {
+          let inside = self.players_in_exit.entry(player_id).or_insert(0);
+          if started { *inside += 1 } else { *inside = inside.saturating_sub(1) }
+          if *inside == 0 { self.players_in_exit.remove(&player_id); }
+          self.status_changed = true;
+        }
+      }
       _ => unimplemented!()
     }
     false
   }
 
-  pub fn step_animations(&mut self, physics: &mut Physics) {
+  #[tracing::instrument(level = "debug", skip_all)]
+  // Ticks is how far animations move, in ticks at the default tick rate
+  pub(crate) fn step_animations(&mut self, physics: &mut Physics, ticks: f32) {
     for id in &self.animated.clone() {
       let object = self.objects.get_mut(*id).unwrap();
       if object.frozen { continue }
-      let new_pos = object.animation.as_mut().unwrap().step();
-      self.set_rapier_pos(*id, physics.body_sets().0, new_pos);
+      let carry = object.carry;
+      let new_pos = object.animation.as_mut().unwrap().step(ticks);
+      let delta = new_pos - self.get_rapier_pos(*id, physics.body_sets().0);
+      self.set_kinematic_pos(*id, physics.body_sets().0, new_pos);
+      if !carry || delta == IVec2::ZERO { continue }
+      let platform = *self.list.get(id).unwrap();
+      let riders: Vec<usize> = self.players.iter()
+        .filter(|player| physics.touching(platform, *self.list.get(player).unwrap()))
+        .copied().collect();
+      // Pushed a little every tick rather than teleported, so riders still collide on the way.
+      // Rapier damps after moving them, which this balances out at the platform's speed
+      let dt = physics.dt();
+      for rider in riders {
+        let body = physics.body_sets().0.get_mut(*self.list.get(&rider).unwrap()).unwrap();
+        let damping = body.linear_damping();
+        let carry = delta.as_vec2() * damping / (1.0 + dt * damping);
+        body.set_linvel(body.linvel() + Vector2::new(carry.x, carry.y), true);
+      }
     }
   }
 
@@ -138,6 +287,23 @@ impl Level {
         *old_pos = new_pos;
       }
     }
+    for id in &self.dynamic {
+      let body = rigids.get(*self.list.get(id).unwrap()).unwrap();
+      let (translation, rotation) = (body.translation(), body.rotation().angle());
+      let new_pos = IVec2::new(translation.x as i32, translation.y as i32);
+      let object = self.objects.get_mut(*id).unwrap();
+      if object.position != new_pos {
+        state_changes.entry(*id)
+          .or_insert(ObjectUpdate::new()).position(new_pos);
+        object.position = new_pos;
+      }
+      // Only resend the shape once the rotation is visible
+      if (object.rotation - rotation).abs() > 0.01 {
+        object.rotation = rotation;
+        state_changes.entry(*id)
+          .or_insert(ObjectUpdate::new()).shape(object.shape());
+      }
+    }
     for id in &self.players {
       let new_pos = self.get_rapier_pos(*id, rigids);
       let old_pos = &mut self.objects.get_mut(*id).unwrap().position;
@@ -152,7 +318,7 @@ impl Level {
 }
 
 impl Level {
-  pub fn new(level: String, physics: &mut Physics) -> Self {
+  pub(crate) fn new(level: String, physics: &mut Physics) -> Self {
     physics.reset();
     let mut new = Self { 
       objects: Pond::new(),
@@ -160,36 +326,112 @@ impl Level {
       next: None,
       current: level.clone(),
       players: HashSet::new(),
+      bots: HashSet::new(),
       animated: HashSet::new(),
+      dynamic: HashSet::new(),
       events: Mutex::new(Vec::new()),
       players_on_button: HashMap::new(),
+      requirements: [Requirement::Absolute(1); 16],
       button_requirements: [1; 16],
+      channels_held: [0; 16],
+      channels: Vec::new(),
+      status_changed: true,
+      teams: 1,
+      collected: HashMap::new(),
+      total_collected: 0,
+      players_in_exit: HashMap::new(),
+      win: Condition::Channel(0),
+      fail: None,
+      elapsed: Duration::ZERO,
+      time_limit: None,
+      won: false,
+      deaths: 0,
+      wins: 0,
       receivers: HashSet::new(),
+      motors: Vec::new(),
     };
-    let json = std::fs::read_to_string(
-      format!("{}/levels/{}", env!("CARGO_MANIFEST_DIR"), level)
-    ).unwrap();
+    let json = std::fs::read_to_string(Self::path(&level)).unwrap();
     let deser_level: InitialLevel = serde_json::from_str(&json).unwrap();
-    new.button_requirements = deser_level.buttons;
+    new.requirements = deser_level.buttons;
+    new.update_requirements();
+    new.teams = deser_level.teams.clamp(1, MAX_TEAMS);
+    new.win = match deser_level.collect {
+      Some(required) => Condition::Any(vec![deser_level.win, Condition::Collected(required)]),
+      None => deser_level.win,
+    };
+    new.time_limit = deser_level.time_limit;
+    new.fail = match (deser_level.fail, deser_level.time_limit) {
+      (Some(fail), Some(limit)) => Some(Condition::Any(vec![fail, Condition::Survive(limit)])),
+      (fail, limit) => fail.or(limit.map(Condition::Survive)),
+    };
     new.next = deser_level.next;
+    let mut ids = Vec::new();
+    new.win.channels(&mut new.channels);
     for min_obj in deser_level.objects {
       let recievers = min_obj.receivers.clone();
-      new.add_object(min_obj.full_rect(), recievers, physics, false);
+      if let Material::Button(channel, _) = min_obj.material { new.channels.push(channel as u8 % 16); }
+      ids.push(new.add_object(min_obj.full_rect(), recievers, physics, false));
     }
+    for min_joint in deser_level.joints {
+      let joint = match min_joint.full_joint(ids.len()) {
+        Ok(joint) => joint,
+        Err(error) => { warn!(level, error, "skipping joint"); continue }
+      };
+      let body_1 = *new.list.get(&ids[min_joint.objects.0]).unwrap();
+      let body_2 = *new.list.get(&ids[min_joint.objects.1]).unwrap();
+      let handle = physics.insert_joint(body_1, body_2, joint);
+      if let Some((axis, motor)) = min_joint.motor() {
+        if let Some(channel) = motor.channel { new.channels.push(channel); }
+        new.motors.push(JointMotor { handle, axis, motor, active: None });
+      }
+    }
+    new.channels.sort();
+    new.channels.dedup();
     new
   }
 
-  pub fn delete(&mut self, id: usize, physics: &mut Physics) {
+  // The roster changed, so the player count in the status did too. Away players' frozen mice
+  // are left out, or a dropped player would keep requirements up for the whole grace period
+  fn update_requirements(&mut self) {
+    let players = self.players.iter().filter(|id| !self.objects.get(**id).unwrap().frozen).count();
+    self.button_requirements = self.requirements.map(|requirement| requirement.effective(players));
+    self.status_changed = true;
+  }
+
+  pub(crate) fn path(level: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("levels").join(level)
+  }
+
+  // Whether a level file with this name can be loaded
+  pub(crate) fn exists(level: &str) -> bool {
+    let plain_name = !level.is_empty() && level.chars().all(|char| char.is_ascii_alphanumeric() || char == '_' || char == '-');
+    plain_name && Self::path(level).is_file()
+  }
+
+  pub fn available() -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(Self::path("")) else { return Vec::new() };
+    let mut levels: Vec<String> = entries.filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+      .filter(|level| Self::exists(level))
+      .collect();
+    levels.sort();
+    levels
+  }
+
+  pub(crate) fn delete(&mut self, id: usize, physics: &mut Physics) {
     self.objects.free(id);
     let handle = self.list.remove(&id).unwrap();
-    self.players.remove(&id);
+    if self.players.remove(&id) { self.update_requirements(); }
+    self.bots.remove(&id);
     self.animated.remove(&id);
+    self.dynamic.remove(&id);
     self.players_on_button.remove(&id);
+    self.collected.remove(&id);
+    self.players_in_exit.remove(&id);
     self.receivers.retain(|controller| controller.id != id);
     physics.remove(handle);
   }
 
-  pub fn add_object(&mut self, object: Object, receivers: Vec<(Action, u8)>, physics: &mut Physics, player: bool) -> usize {
+  pub(crate) fn add_object(&mut self, object: Object, receivers: Vec<(Action, u8)>, physics: &mut Physics, player: bool) -> usize {
     let id = self.objects.alloc(object);
     let object = self.objects.get_mut(id).unwrap();
     object.rigidbody.user_data = id as u128;
@@ -202,7 +444,10 @@ impl Level {
     );
     self.list.insert(id, rb_handle);
     if object.animation.is_some() { self.animated.insert(id); }
-    if player { self.players.insert(id); }
+    if player {
+      self.players.insert(id);
+      self.update_requirements();
+    } else if object.rigidbody.is_dynamic() { self.dynamic.insert(id); }
     for (action, channel) in receivers {
       self.receivers.insert(RemoteControl {
         id,
@@ -214,16 +459,73 @@ impl Level {
     id
   }
 
-  pub fn apply_vel(&mut self, rigids: &mut RigidBodySet, id: usize, velocity: IVec2) {
-    if !self.players.contains(&id) { dbg!("Failed to add velocity to collider"); return; }
+  pub(crate) fn apply_vel(&mut self, rigids: &mut RigidBodySet, id: usize, velocity: IVec2) {
+    if !self.players.contains(&id) { tracing::error!(object = id, "failed to add velocity to collider"); return; }
     let handle = self.list.get(&id).unwrap();
     let rb = rigids.get_mut(*handle).unwrap();
     let mass = rb.mass();
     let impulse = velocity.as_vec2() * mass;
-    rb.apply_impulse(Vector2::new(impulse.x as f32, impulse.y as f32), true);
+    rb.apply_impulse(Vector2::new(impulse.x, impulse.y), true);
+  }
+
+  pub(crate) fn players_in_exit(&self) -> usize { self.players_in_exit.len() }
+
+  pub(crate) fn collect_goal(&self) -> Option<u32> { self.win.collect_goal() }
+
+  // Whole seconds, rounded up, until the time limit restarts the level
+  pub(crate) fn time_left(&self) -> Option<u32> {
+    if self.won { return None }
+    let left = Duration::from_secs(self.time_limit? as u64).saturating_sub(self.elapsed);
+    Some(left.as_secs_f32().ceil() as u32)
   }
 
-  pub fn get_obj(&self, id: usize) -> Option<&Object> { self.objects.get(id) }
+  pub(crate) fn collected_by(&self, player: usize) -> u32 { self.collected.get(&player).copied().unwrap_or(0) }
+
+  // Whether winning has players leave the buttons for an exit
+  pub(crate) fn needs_exit(&self) -> bool { self.win.needs_exit() }
+
+  // Players it takes to hold every channel at once, with this many in the level
+  pub(crate) fn players_needed(&self, players: usize) -> usize {
+    self.channels.iter().map(|channel| self.requirements[*channel as usize].effective(players) as usize).sum()
+  }
+
+  // In id order, so anything deciding from it stays deterministic
+  pub(crate) fn objects(&self) -> Vec<(usize, &Object)> {
+    let mut ids: Vec<usize> = self.list.keys().copied().collect();
+    ids.sort();
+    ids.into_iter().filter_map(|id| Some((id, self.objects.get(id)?))).collect()
+  }
+
+  // Box around the level itself, ignoring mice and pings, which can wander anywhere
+  pub(crate) fn bounds(&self) -> (IVec2, IVec2) {
+    let (min, max) = self.objects().into_iter()
+      .filter(|(id, object)| !self.players.contains(id) && !matches!(object.material, Material::Ping))
+      .map(|(_, object)| object.bounds())
+      .fold((IVec2::MAX, IVec2::MIN), |(min, max), bounds| (min.min(bounds.0), max.max(bounds.1)));
+    if min.x > max.x { (IVec2::ZERO, IVec2::ZERO) } else { (min, max) }
+  }
+
+  // The team with the fewest players, so teams stay balanced as players join
+  pub(crate) fn next_team(&self) -> u8 {
+    let mut members = vec![0; self.teams as usize];
+    for id in &self.players {
+      let team = self.objects.get(*id).unwrap().team;
+      if let Some(count) = members.get_mut(team as usize) { *count += 1; }
+    }
+    (0..self.teams).min_by_key(|team| members[*team as usize]).unwrap()
+  }
+
+  // Frozen players can't move or be pushed, but keep holding whatever they're standing on
+  pub(crate) fn freeze_player(&mut self, rigids: &mut RigidBodySet, id: usize, frozen: bool) {
+    let Some(object) = self.objects.get_mut(id) else { return };
+    object.frozen = frozen;
+    let body = rigids.get_mut(*self.list.get(&id).unwrap()).unwrap();
+    body.set_locked_axes(if frozen { LockedAxes::all() } else { LockedAxes::ROTATION_LOCKED }, true);
+    body.set_linvel(Vector2::zeros(), true);
+    self.update_requirements();
+  }
+
+  pub(crate) fn get_obj(&self, id: usize) -> Option<&Object> { self.objects.get(id) }
 
   fn get_rapier_pos(&self, id: usize, rigids: &RigidBodySet) -> IVec2 {
     let handle = self.list.get(&id).unwrap();
@@ -236,6 +538,11 @@ impl Level {
     rigids.get_mut(*handle).unwrap().set_position(Translation::new(pos.x as f32, pos.y as f32).into(), true);
   }
 
+  fn set_kinematic_pos(&mut self, id: usize, rigids: &mut RigidBodySet, pos: IVec2) {
+    let handle = self.list.get(&id).unwrap();
+    rigids.get_mut(*handle).unwrap().set_next_kinematic_position(Translation::new(pos.x as f32, pos.y as f32).into());
+  }
+
 }
 
 fn id_from_collider(handle: ColliderHandle, rigids: &RigidBodySet, colliders: &ColliderSet) -> Option<usize> {
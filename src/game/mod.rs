@@ -3,8 +3,20 @@ mod serde;
 mod level;
 mod object;
 mod state;
+mod player;
+mod chat;
+mod rate_limit;
+mod admin;
+mod replay;
+mod simulation;
+mod bots;
 
 pub use state::{GameState, ObjectUpdate};
-pub use physics::Physics;
+pub(crate) use physics::Physics;
 pub use level::Level;
-pub use object::{Object, Material};
+pub use admin::{Command, spawn_console};
+pub use replay::replay;
+pub use simulation::Simulation;
+pub(crate) use object::Object;
+pub use object::Material;
+pub use player::{Identity, ClientMessage, ServerMessage};
@@ -1,3 +1,4 @@
+use std::sync::OnceLock;
 use glam::IVec2;
 use rapier2d::prelude::*;
 use rapier2d::na::Vector2;
@@ -14,20 +15,21 @@ pub struct Path {
   last_checkpoint: IVec2,
   steps: Vec<Step>,
   current_step: u32,
-  current_tick: u32,
-  ticks_sleeping: u32,
+  // Both counted in ticks at the default tick rate, and advanced by fractions of one at higher rates
+  current_tick: f32,
+  ticks_sleeping: f32,
 }
 impl Path {
-  pub fn step(&mut self) -> IVec2 {
-    if self.ticks_sleeping != 0 { self.ticks_sleeping -= 1; }
-    else { self.current_tick += 1; }
+  pub fn step(&mut self, ticks: f32) -> IVec2 {
+    if self.ticks_sleeping > 0.0 { self.ticks_sleeping = (self.ticks_sleeping - ticks).max(0.0); }
+    else { self.current_tick += ticks; }
     let step = &self.steps[self.current_step as usize];
-    let interpolate = self.current_tick as f32 / step.duration as f32;
+    let interpolate = self.current_tick / step.duration as f32;
     let current = self.last_checkpoint.as_vec2().lerp(step.destination.as_vec2(), interpolate);
-    if self.current_tick > step.duration {
-      self.current_tick = 0;
+    if self.current_tick > step.duration as f32 {
+      self.current_tick = 0.0;
       self.last_checkpoint = step.destination;
-      self.ticks_sleeping = step.sleep;
+      self.ticks_sleeping = step.sleep as f32;
       self.current_step += 1;
       self.current_step %= self.steps.len() as u32;
     }
@@ -35,6 +37,13 @@ impl Path {
   }
 }
 
+// Teams own the low collision group bits, everything that isn't a player sits in WORLD_GROUP
+pub const MAX_TEAMS: u8 = 16;
+pub const PINK_TEAM: u8 = 1;
+const WORLD_GROUP: Group = Group::GROUP_32;
+
+fn team_group(team: u8) -> Group { Group::from_bits_truncate(1 << team) }
+
 pub struct Object {
   pub position: IVec2,
   pub points: Vec<IVec2>,
@@ -44,7 +53,11 @@ pub struct Object {
   pub animation: Option<Path>,
   pub hidden: bool,
   pub frozen: bool,
-
+  // Players touching a carrying platform move along with it
+  pub carry: bool,
+  // Last rotation sent to clients, only dynamic objects rotate
+  pub rotation: f32,
+  pub team: u8,
 }
 impl Object {
   pub fn new_mouse() -> Self {
@@ -57,11 +70,15 @@ impl Object {
       IVec2::new(6, 12),
       IVec2::new(11, 12),
     ];
-    let vertices: Vec<Point<f32>> = points.iter()
-      .map(|point| Point::new(point.x as f32, point.y as f32)).collect();
-    let indices: Vec<[u32; 2]> = (0..vertices.len() as u32)
-      .map(|i| [i, (i + 1) % vertices.len() as u32]).collect();
-    let collider = ColliderBuilder::convex_decomposition(&vertices, &indices).build();
+    // Decomposing is slow (seconds in debug builds), and every mouse has the same shape
+    static MOUSE_COLLIDER: OnceLock<Collider> = OnceLock::new();
+    let collider = MOUSE_COLLIDER.get_or_init(|| {
+      let vertices: Vec<Point<f32>> = points.iter()
+        .map(|point| Point::new(point.x as f32, point.y as f32)).collect();
+      let indices: Vec<[u32; 2]> = (0..vertices.len() as u32)
+        .map(|i| [i, (i + 1) % vertices.len() as u32]).collect();
+      ColliderBuilder::convex_decomposition(&vertices, &indices).build()
+    }).clone();
     let rigidbody = RigidBodyBuilder::new(RigidBodyType::Dynamic)
       .translation(Vector2::zeros())
       .locked_axes(LockedAxes::ROTATION_LOCKED)
@@ -77,6 +94,9 @@ impl Object {
       animation: None,
       hidden: false,
       frozen: false,
+      carry: false,
+      rotation: 0.0,
+      team: 0,
     }
   }
   
@@ -90,16 +110,18 @@ impl Object {
     if material.has_event() {
       collider = collider.active_events(ActiveEvents::COLLISION_EVENTS);
     }
-    let rigidbody = RigidBodyBuilder::new(RigidBodyType::Fixed)
+    // Animated objects are driven kinematically so rapier can push players out of their way
+    let body_type = if animation.is_some() { RigidBodyType::KinematicPositionBased } else { RigidBodyType::Fixed };
+    let rigidbody = RigidBodyBuilder::new(body_type)
       .translation(Vector2::new(top_left.x as f32, top_left.y as f32))
       .build();
-    let animation = if let Some(steps) = animation { Some(Path {
+    let animation = animation.map(|steps| Path {
       last_checkpoint: top_left,
       steps,
       current_step: 0,
-      current_tick: 0,
-      ticks_sleeping: 0,
-    }) } else { None };
+      current_tick: 0.0,
+      ticks_sleeping: 0.0,
+    });
     Self {
       points: vec![IVec2::ZERO, length.with_x(0), length, length.with_y(0)],
       position: top_left,
@@ -109,12 +131,63 @@ impl Object {
       animation,
       hidden: false,
       frozen: false,
+      carry: false,
+      rotation: 0.0,
+      team: 0,
     }
   }
 
+  pub fn on_team(mut self, team: u8) -> Self {
+    self.team = team;
+    self.collider.set_collision_groups(InteractionGroups::new(team_group(team), Group::ALL));
+    self
+  }
+
+  pub fn at(mut self, position: IVec2) -> Self {
+    self.position = position;
+    self.rigidbody.set_translation(Vector2::new(position.x as f32, position.y as f32), false);
+    self
+  }
+
+  // Purely visual markers that nothing collides with
+  pub fn new_ping(center: IVec2) -> Self {
+    let mut ping = Self::new_rect(center - IVec2::splat(6), IVec2::splat(12), Material::Ping, None);
+    ping.collider.set_collision_groups(InteractionGroups::none());
+    ping
+  }
+
+  // Only players on the given teams collide with (or trigger) the object
+  pub fn blocks(&mut self, teams: &[u8]) {
+    let filter = teams.iter().fold(WORLD_GROUP, |filter, team| filter | team_group(*team));
+    self.collider.set_collision_groups(InteractionGroups::new(WORLD_GROUP, filter));
+  }
+
+  // Lets the object be pushed around and swung by joints
+  pub fn make_dynamic(&mut self) {
+    self.rigidbody.set_body_type(RigidBodyType::Dynamic, false);
+    self.rigidbody.set_linear_damping(5.0);
+    self.rigidbody.set_angular_damping(5.0);
+  }
+
+  // Top left and bottom right corners of the box around the object
+  pub fn bounds(&self) -> (IVec2, IVec2) {
+    let shape = self.shape();
+    let min = shape.iter().fold(IVec2::MAX, |min, point| min.min(*point));
+    let max = shape.iter().fold(IVec2::MIN, |max, point| max.max(*point));
+    (self.position + min, self.position + max)
+  }
+
+  // Points rotated into the object's current orientation
+  pub fn shape(&self) -> Vec<IVec2> {
+    let rotation = glam::Vec2::from_angle(self.rotation);
+    self.points.iter()
+      .map(|point| rotation.rotate(point.as_vec2()).round().as_ivec2())
+      .collect()
+  }
+
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize)]
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 pub enum Material {
   Player,
   Wall,
@@ -123,22 +196,16 @@ pub enum Material {
   BigDeath,
   Button(u32, u8),
   Tree,
+  Collectible,
+  Exit,
+  Ping,
 }
 impl Material {
   pub fn is_sensor(&self) -> bool {
-    match self {
-      Self::Button(_, _) => true,
-      Self::Tree => true,
-      _ => false,
-    }
+    matches!(self, Self::Button(_, _) | Self::Tree | Self::Collectible | Self::Exit | Self::Ping)
   }
   pub fn has_event(&self) -> bool {
-    match self {
-      Self::Death => true,
-      Self::BigDeath => true,
-      Self::Button(_, _) => true,
-      _ => false,
-    }
+    matches!(self, Self::Death | Self::BigDeath | Self::Button(_, _) | Self::Collectible | Self::Exit)
   }
   pub fn color(&self) -> i32 {
     match self {
@@ -160,12 +227,22 @@ impl Material {
       Self::Button(x, active)
         if *x == 1 && *active == 0 => 6, // Purple
 
+      Self::Collectible => 7, // Gold
+      Self::Exit => 8,        // Light Blue
+      Self::Ping => 9,        // Orange, tinted by whoever placed it
+
       _ => unimplemented!(),
     }
   }
+  pub fn default_blocks(&self) -> Option<Vec<u8>> {
+    match self {
+      Self::PinkWall => Some((0..MAX_TEAMS).filter(|team| *team != PINK_TEAM).collect()),
+      _ => None,
+    }
+  }
   pub fn set_active(&mut self, activity: bool) {
     if let Self::Button(_, active) = self { 
-      *active = if activity { *active + 1 } else { *active - 1 };
+      *active = if activity { *active + 1 } else { active.saturating_sub(1) };
     }
   }
 }
@@ -1,6 +1,10 @@
 use rapier2d::{na::Vector2, prelude::*};
 
 use super::Level;
+use super::admin::DEFAULT_TICK_RATE;
+
+// Rapier's own step, which levels were tuned with at the default tick rate
+const DEFAULT_DT: f32 = 1.0 / 60.0;
 
 pub struct Physics {
   colliders: ColliderSet,
@@ -16,10 +20,12 @@ pub struct Physics {
 }
 impl Physics {
   pub fn new() -> Self {
-    let mut integration_params = IntegrationParameters::default();
-    integration_params.length_unit = 5.0;
-    integration_params.normalized_max_corrective_velocity = 200.0;
-    integration_params.max_ccd_substeps = 10;
+    let integration_params = IntegrationParameters {
+      length_unit: 5.0,
+      normalized_max_corrective_velocity: 200.0,
+      max_ccd_substeps: 10,
+      ..Default::default()
+    };
     Self {
       colliders: ColliderSet::new(),
       rigids: RigidBodySet::new(),
@@ -29,7 +35,7 @@ impl Physics {
       narrow_phase: NarrowPhase::new(),
       impulse_joints: ImpulseJointSet::new(),
       multibody_joints: MultibodyJointSet::new(),
-      ccd_solver: CCDSolver::default(),
+      ccd_solver: CCDSolver,
       integration_params,
     }
   }
@@ -38,10 +44,36 @@ impl Physics {
     self.rigids.remove(handle, &mut self.islands, &mut self.colliders, &mut self.impulse_joints, &mut self.multibody_joints, true);
   }
 
+  // Shorter steps at higher tick rates, so the game runs at the same speed and only gets smoother
+  pub fn set_tick_rate(&mut self, tick_rate: u32) {
+    self.integration_params.dt = DEFAULT_DT * DEFAULT_TICK_RATE as f32 / tick_rate as f32;
+  }
+
+  pub fn dt(&self) -> f32 { self.integration_params.dt }
+
+  pub fn insert_joint(&mut self, body_1: RigidBodyHandle, body_2: RigidBodyHandle, joint: GenericJoint) -> ImpulseJointHandle {
+    self.impulse_joints.insert(body_1, body_2, joint, true)
+  }
+
+  pub fn joint_mut(&mut self, handle: ImpulseJointHandle) -> Option<&mut GenericJoint> {
+    self.impulse_joints.get_mut(handle, true).map(|joint| &mut joint.data)
+  }
+
+  pub fn body(&self, handle: RigidBodyHandle) -> Option<&RigidBody> { self.rigids.get(handle) }
+
   pub fn body_sets(&mut self) -> (&mut RigidBodySet, &mut ColliderSet) {
     (&mut self.rigids, &mut self.colliders)
   }
 
+  // Whether any collider of body a is in contact with (or overlapping) any collider of body b
+  pub fn touching(&self, a: RigidBodyHandle, b: RigidBodyHandle) -> bool {
+    let (Some(body_a), Some(body_b)) = (self.rigids.get(a), self.rigids.get(b)) else { return false };
+    body_a.colliders().iter().any(|collider_a| body_b.colliders().iter().any(|collider_b| {
+      self.narrow_phase.contact_pair(*collider_a, *collider_b).is_some_and(|pair| pair.has_any_active_contact)
+        || self.narrow_phase.intersection_pair(*collider_a, *collider_b) == Some(true)
+    }))
+  }
+
   pub fn reset(&mut self) {
     self.rigids = RigidBodySet::new();
     self.colliders = ColliderSet::new();
@@ -51,9 +83,10 @@ impl Physics {
     self.narrow_phase = NarrowPhase::new();
     self.impulse_joints = ImpulseJointSet::new();
     self.multibody_joints = MultibodyJointSet::new();
-    self.ccd_solver = CCDSolver::default();
+    self.ccd_solver = CCDSolver;
   }
 
+  #[tracing::instrument(level = "debug", skip_all)]
   pub fn step(&mut self, level: &mut Level) {
     self.pipeline.step(
       &Vector2::zeros(),
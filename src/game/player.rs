@@ -0,0 +1,89 @@
+use std::time::Duration;
+use glam::IVec2;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Handed out in order whenever a player's preferred color is taken
+const PALETTE: [u32; 12] = [
+  0xe6194b, 0x3cb44b, 0xffe119, 0x4363d8, 0xf58231, 0x911eb4,
+  0x46f0f0, 0xf032e6, 0xbcf60c, 0xfabebe, 0x008080, 0x9a6324,
+];
+const MAX_NAME_LENGTH: usize = 16;
+
+#[derive(Clone)]
+pub struct Identity {
+  pub name: String,
+  pub color: u32, // 0xRRGGBB
+}
+impl Identity {
+  // Picks the preferred color unless someone else already has it
+  pub fn new(name: &str, preferred: Option<u32>, taken: &[u32]) -> Self {
+    let name: String = name.trim().chars()
+      .filter(|char| !char.is_control())
+      .take(MAX_NAME_LENGTH).collect();
+    let name = if name.is_empty() { "Mouse".to_owned() } else { name };
+    let color = preferred.map(|color| color & 0xffffff)
+      .filter(|color| !taken.contains(color))
+      .or(PALETTE.into_iter().find(|color| !taken.contains(color)))
+      .unwrap_or(PALETTE[taken.len() % PALETTE.len()]);
+    Self { name, color }
+  }
+
+  // How everyone else sees the player while they're disconnected
+  pub fn away(&self) -> Self {
+    Self { name: format!("{} (away)", self.name), color: self.color }
+  }
+}
+
+// Disconnected players keep their mouse this long in case they come back
+pub const GRACE_PERIOD: Duration = Duration::from_secs(30);
+// Anyone connecting past this spectates until a slot frees up
+pub const MAX_PLAYERS: usize = 16;
+pub const PING_LIFETIME: Duration = Duration::from_secs(3);
+// Each ping is a physics body, so a player can't place them any faster than this
+pub const PING_COOLDOWN: Duration = Duration::from_millis(300);
+// Placing another ping past this replaces the player's oldest one
+pub const MAX_PINGS: usize = 3;
+
+pub struct Ping {
+  pub object_id: usize,
+  pub owner: Uuid,
+  pub placed: Duration, // Game time
+  pub expires: Duration,
+}
+
+// A disconnected player's frozen mouse, waiting to be resumed
+pub struct Ghost {
+  pub object_id: usize,
+  pub identity: Identity,
+  pub expires: Duration, // Game time
+}
+
+// Sent by clients as json text messages
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+  Join { name: String, color: Option<u32> },
+  Resume { token: Uuid },
+  Play,
+  Spectate,
+  Chat { text: String },
+  Ping { position: Option<IVec2> }, // Defaults to the player's mouse
+  Admin { secret: String },
+  Command { command: String }, // Only accepted from admins
+}
+
+// Sent to clients as json text messages
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum ServerMessage {
+  // Always the first message, object_id is the mouse we control unless spectating
+  Hello { version: u32, tick_rate: u32, object_id: Option<usize>, features: Vec<String> },
+  Rejected { reason: String }, // Sent right before the server hangs up
+  Session { token: Uuid }, // Send back in a Resume to reclaim this mouse after a reconnect
+  Role { spectating: bool },
+  Welcome { object_id: usize }, // Our mouse, sent again whenever it changes (ie. every level load)
+  Chat { name: String, color: Option<u32>, text: String },
+  Admin { authenticated: bool },
+  Notice { text: String }, // Result of an admin command
+}
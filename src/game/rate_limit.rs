@@ -0,0 +1,26 @@
+use std::{collections::{HashMap, VecDeque}, time::Duration};
+use uuid::Uuid;
+
+// At most `limit` hits per connection within `window`, timed by uptime so it also runs
+// while the game is frozen
+pub struct RateLimit {
+  limit: usize,
+  window: Duration,
+  recent: HashMap<Uuid, VecDeque<Duration>>, // Connection, uptimes it was last hit at
+}
+impl RateLimit {
+  pub fn new(limit: usize, window: Duration) -> Self {
+    Self { limit, window, recent: HashMap::new() }
+  }
+
+  // Whether the connection used up its hits for now
+  pub fn limited(&mut self, id: Uuid, now: Duration) -> bool {
+    let hits = self.recent.entry(id).or_default();
+    while hits.front().is_some_and(|hit_at| *hit_at + self.window <= now) { hits.pop_front(); }
+    hits.len() >= self.limit
+  }
+
+  pub fn hit(&mut self, id: Uuid, now: Duration) { self.recent.entry(id).or_default().push_back(now); }
+
+  pub fn forget(&mut self, id: Uuid) { self.recent.remove(&id); }
+}
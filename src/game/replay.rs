@@ -0,0 +1,94 @@
+use std::{collections::VecDeque, fs::File, io::{BufRead, BufReader, BufWriter, Write}};
+use glam::IVec2;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+use uuid::Uuid;
+use crate::{networking::Server, protocol::PROTOCOL_VERSION};
+use super::{Command, GameState};
+
+fn current_version() -> u32 { PROTOCOL_VERSION }
+
+// Everything from outside the game that changes its state
+#[derive(Serialize, Deserialize, Clone)]
+pub enum Input {
+  Connect {
+    id: Uuid,
+    spectate: bool,
+    #[serde(default = "current_version")] // Recorded before clients announced one
+    version: u32,
+  },
+  Disconnect(Uuid),
+  Move(Uuid, IVec2),
+  Text(Uuid, String), // Json client message
+  AdminLogin(Uuid, bool), // Whether it succeeded, recorded instead of the secret
+  Command(Command),
+  Token(Uuid), // Resume tokens are random, so replays reuse the recorded ones
+}
+
+// First line of a replay file, followed by one ReplayTick per line
+#[derive(Serialize, Deserialize)]
+struct ReplayHeader {
+  level: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ReplayTick {
+  inputs: Vec<Input>,
+  checksum: u64, // Of every object's position after the tick
+}
+
+pub struct Recorder {
+  file: BufWriter<File>,
+  tick: ReplayTick,
+}
+impl Recorder {
+  pub fn new(path: &str, level: &str) -> std::io::Result<Self> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "{}", serde_json::to_string(&ReplayHeader { level: level.to_owned() })?)?;
+    Ok(Self { file, tick: ReplayTick::default() })
+  }
+
+  pub fn input(&mut self, input: &Input) { self.tick.inputs.push(input.clone()); }
+
+  pub fn finish_tick(&mut self, checksum: u64) {
+    self.tick.checksum = checksum;
+    let line = serde_json::to_string(&std::mem::take(&mut self.tick)).unwrap();
+    // Flushed every tick so a killed server still leaves a usable recording
+    if let Err(error) = writeln!(self.file, "{line}").and_then(|_| self.file.flush()) {
+      error!(%error, "failed to write replay");
+    }
+  }
+}
+
+// Feeds a recorded session back through the game, returning the first tick that diverged
+pub fn replay(path: &str) -> Result<Option<u64>, String> {
+  let mut lines = BufReader::new(File::open(path).map_err(|error| error.to_string())?).lines();
+  let header = lines.next().ok_or("empty replay")?.map_err(|error| error.to_string())?;
+  let header: ReplayHeader = serde_json::from_str(&header).map_err(|error| error.to_string())?;
+  let mut state = GameState::new(header.level);
+  let mut server = Server::offline();
+  for (tick, line) in lines.enumerate() {
+    let line = line.map_err(|error| error.to_string())?;
+    let recorded: ReplayTick = serde_json::from_str(&line).map_err(|error| error.to_string())?;
+    let (tokens, inputs): (Vec<Input>, Vec<Input>) = recorded.inputs.into_iter()
+      .partition(|input| matches!(input, Input::Token(_)));
+    state.replayed_tokens = tokens.into_iter()
+      .filter_map(|input| if let Input::Token(token) = input { Some(token) } else { None })
+      .collect::<VecDeque<_>>();
+    for input in inputs { state.apply(&mut server, input); }
+    state.step();
+    state.state_changes.clear();
+    if state.checksum() != recorded.checksum {
+      error!(tick, "replay diverged");
+      return Ok(Some(tick as u64));
+    }
+  }
+  info!("replay matched");
+  Ok(None)
+}
+
+// FNV-1a, stable across Rust versions unlike DefaultHasher
+pub fn fnv(hash: u64, bytes: &[u8]) -> u64 {
+  bytes.iter().fold(hash, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+pub const FNV_OFFSET: u64 = 0xcbf29ce484222325;
@@ -1,32 +1,202 @@
 use crate::game::Object;
 use super::Material;
-use super::object::Step;
-use glam::IVec2;
+use super::object::{Step, MAX_TEAMS};
+use glam::{IVec2, Vec2};
+use rapier2d::prelude::*;
+use tracing::warn;
 use serde::Deserialize;
 use super::level::Action;
 
+// Typos in a level file would otherwise quietly fall back to defaults
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct InitialLevel {
-  pub buttons: [u8; 16],
+  pub buttons: [Requirement; 16],
+  #[serde(default = "one_team")]
+  pub teams: u8,
   pub next: Option<String>,
+  #[serde(default = "channel_zero")]
+  pub win: Condition,
+  pub fail: Option<Condition>, // Restarts the level when met
+  pub time_limit: Option<u32>, // Seconds before the level restarts
+  pub collect: Option<u32>, // Older shorthand, also wins once this many are collected
   pub objects: Vec<MinimalRect>,
+  #[serde(default)]
+  pub joints: Vec<MinimalJoint>,
+}
+
+// Plain numbers are absolute, otherwise scaled by the connected player count
+#[derive(Deserialize, Clone, Copy)]
+#[serde(untagged)]
+pub enum Requirement {
+  Absolute(u8),
+  Scaled(Scaling),
+}
+#[derive(Deserialize, Clone, Copy)]
+pub enum Scaling {
+  Fraction(f32), // Rounded up
+  Min(u8), // min(n, players)
+}
+impl Requirement {
+  // Never drops below one so an empty server can't hold a channel
+  pub fn effective(&self, players: usize) -> u8 {
+    let players = players.min(u8::MAX as usize) as u8;
+    let required = match self {
+      Self::Absolute(required) => *required,
+      Self::Scaled(Scaling::Fraction(fraction)) => (players as f32 * fraction).ceil() as u8,
+      Self::Scaled(Scaling::Min(required)) => (*required).min(players),
+    };
+    required.max(1)
+  }
+}
+
+fn one_team() -> u8 { 1 }
+fn channel_zero() -> Condition { Condition::Channel(0) }
+
+#[derive(Deserialize, Clone)]
+pub enum Condition {
+  Channel(u8), // Enough players holding the channel's buttons
+  AllInExit, // Every player is standing in an exit
+  Collected(u32),
+  Survive(u32), // Seconds since the level started
+  All(Vec<Condition>),
+  Any(Vec<Condition>),
+  Not(Box<Condition>),
+}
+impl Condition {
+  pub fn channels(&self, channels: &mut Vec<u8>) {
+    match self {
+      Self::Channel(channel) => channels.push(*channel % 16),
+      Self::All(conditions) | Self::Any(conditions) => conditions.iter()
+        .for_each(|condition| condition.channels(channels)),
+      Self::Not(condition) => condition.channels(channels),
+      _ => (),
+    }
+  }
+
+  // The most items any part of the condition asks for
+  pub fn collect_goal(&self) -> Option<u32> {
+    match self {
+      Self::Collected(required) => Some(*required),
+      Self::All(conditions) | Self::Any(conditions) => conditions.iter().filter_map(Self::collect_goal).max(),
+      Self::Not(condition) => condition.collect_goal(),
+      _ => None,
+    }
+  }
+
+  pub fn needs_exit(&self) -> bool {
+    match self {
+      Self::AllInExit => true,
+      Self::All(conditions) | Self::Any(conditions) => conditions.iter().any(Self::needs_exit),
+      Self::Not(condition) => condition.needs_exit(),
+      _ => false,
+    }
+  }
 }
 
 #[derive(Deserialize)]
 pub struct MinimalRect {
   position: IVec2,
   length: IVec2,
-  material: Material,
+  pub material: Material,
   animation: Option<Vec<Step>>,
+  #[serde(default)]
+  carry: bool,
+  #[serde(default)]
+  dynamic: bool,
+  blocks: Option<Vec<u8>>, // Teams this object blocks, everyone if unset
   pub receivers: Vec<(Action, u8)>, // (Action, channel)
 }
 impl MinimalRect {
   pub fn full_rect(&self) -> Object {
-    Object::new_rect(
+    let mut object = Object::new_rect(
       self.position,
       self.length,
       self.material,
       self.animation.clone(),
-    )
+    );
+    object.carry = self.carry;
+    if self.dynamic { object.make_dynamic(); }
+    if let Some(mut teams) = self.blocks.clone().or(self.material.default_blocks()) {
+      // Higher teams would spill into the world group, or past the last one
+      if teams.iter().any(|team| *team >= MAX_TEAMS) {
+        warn!(?teams, "ignoring blocked teams past {MAX_TEAMS}");
+        teams.retain(|team| *team < MAX_TEAMS);
+      }
+      object.blocks(&teams);
+    }
+    object
+  }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct Motor {
+  // Target velocity while the channel is held (or always, without a channel)
+  pub speed: f32,
+  // Target velocity while the channel isn't held
+  #[serde(default)]
+  pub idle_speed: f32,
+  pub factor: f32,
+  pub channel: Option<u8>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum JointKind {
+  Revolute { limits: Option<[f32; 2]>, motor: Option<Motor> },
+  Prismatic { axis: Vec2, limits: Option<[f32; 2]>, motor: Option<Motor> },
+  Rope { length: f32 },
+}
+
+// Joins two objects, referenced by their index in the level's objects
+#[derive(Deserialize)]
+pub struct MinimalJoint {
+  pub objects: (usize, usize),
+  pub anchors: (IVec2, IVec2), // Relative to each object's top left
+  pub kind: JointKind,
+}
+impl MinimalJoint {
+  // Objects is how many the level has
+  pub fn full_joint(&self, objects: usize) -> Result<GenericJoint, String> {
+    let (object_1, object_2) = self.objects;
+    if object_1.max(object_2) >= objects { return Err(format!("no object {} to join", object_1.max(object_2))) }
+    if object_1 == object_2 { return Err(format!("object {object_1} can't be joined to itself")) }
+    let anchor_1 = point![self.anchors.0.x as f32, self.anchors.0.y as f32];
+    let anchor_2 = point![self.anchors.1.x as f32, self.anchors.1.y as f32];
+    match self.kind {
+      JointKind::Revolute { limits, motor } => {
+        let mut joint = RevoluteJointBuilder::new()
+          .local_anchor1(anchor_1)
+          .local_anchor2(anchor_2)
+          .contacts_enabled(false);
+        if let Some(limits) = limits { joint = joint.limits(limits); }
+        if let Some(motor) = motor { joint = joint.motor_velocity(motor.idle_speed, motor.factor); }
+        Ok(joint.into())
+      }
+      JointKind::Prismatic { axis, limits, motor } => {
+        let axis = UnitVector::try_new(vector![axis.x, axis.y], f32::EPSILON)
+          .ok_or("prismatic joints need a non-zero axis")?;
+        let mut joint = PrismaticJointBuilder::new(axis)
+          .local_anchor1(anchor_1)
+          .local_anchor2(anchor_2)
+          .contacts_enabled(false);
+        if let Some(limits) = limits { joint = joint.limits(limits); }
+        if let Some(motor) = motor { joint = joint.motor_velocity(motor.idle_speed, motor.factor); }
+        Ok(joint.into())
+      }
+      JointKind::Rope { length } => Ok(RopeJointBuilder::new(length)
+        .local_anchor1(anchor_1)
+        .local_anchor2(anchor_2)
+        .into()),
+    }
+  }
+
+  // Channels wrap at 16 like the buttons that hold them
+  pub fn motor(&self) -> Option<(JointAxis, Motor)> {
+    let (axis, motor) = match self.kind {
+      JointKind::Revolute { motor, .. } => (JointAxis::AngX, motor?),
+      JointKind::Prismatic { motor, .. } => (JointAxis::LinX, motor?),
+      JointKind::Rope { .. } => return None,
+    };
+    Some((axis, Motor { channel: motor.channel.map(|channel| channel % 16), ..motor }))
   }
 }
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use axum::extract::ws::Message;
+use glam::IVec2;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use uuid::Uuid;
+use crate::{networking::{Event, Server}, protocol::PROTOCOL_VERSION};
+use super::{ClientMessage, Command, GameState, Material, ObjectUpdate, ServerMessage};
+use super::bots::{arrived, steer};
+use super::replay::Input;
+
+// Runs a level headless with scripted players, for testing levels without browsers
+pub struct Simulation {
+  state: GameState,
+  server: Server,
+  next_player: u128,
+  inboxes: HashMap<Uuid, UnboundedReceiver<Event>>, // What the server sent each player
+}
+impl Simulation {
+  pub fn new(level: &str) -> Self {
+    Self { state: GameState::new(level.to_owned()), server: Server::offline(), next_player: 1, inboxes: HashMap::new() }
+  }
+
+  // Everything from here on is saved for replay(), like the server's --record
+  pub fn record(&mut self, path: &str) -> std::io::Result<()> {
+    self.state.record(path)
+  }
+
+  // Players get sequential ids so failing runs are reproducible
+  pub fn add_player(&mut self) -> Uuid {
+    let id = Uuid::from_u128(self.next_player);
+    self.next_player += 1;
+    let (sender, inbox) = unbounded_channel();
+    self.server.list.insert(id, sender);
+    self.inboxes.insert(id, inbox);
+    self.state.apply(&mut self.server, Input::Connect { id, spectate: false, version: PROTOCOL_VERSION });
+    id
+  }
+
+  pub fn remove_player(&mut self, id: Uuid) {
+    self.state.apply(&mut self.server, Input::Disconnect(id));
+  }
+
+  // Same as a client's mouse movement, felt on the next tick
+  pub fn push(&mut self, id: Uuid, delta: IVec2) {
+    self.state.apply(&mut self.server, Input::Move(id, delta));
+  }
+
+  pub fn send(&mut self, id: Uuid, message: &ClientMessage) {
+    let text = serde_json::to_string(message).unwrap();
+    self.state.apply(&mut self.server, Input::Text(id, text));
+  }
+
+  pub fn set_admin_secret(&mut self, secret: &str) { self.state.set_admin_secret(Some(secret.to_owned())); }
+
+  pub fn command(&mut self, command: Command) {
+    self.state.apply(&mut self.server, Input::Command(command));
+  }
+
+  pub fn step(&mut self, ticks: u32) {
+    for _ in 0..ticks {
+      // Same as the server loop, so recordings get their checksums
+      self.state.tick();
+      self.state.send_welcomes(&mut self.server);
+    }
+  }
+
+  // The binary broadcasts the server would send now, covering everything since the last one
+  pub fn state_message(&mut self) -> Option<Vec<i32>> { self.state.state_message() }
+
+  pub fn channels_message(&mut self) -> Option<Vec<i32>> { self.state.channels_message() }
+
+  // What a client joining now would be sent for the object
+  pub fn full_update(&self, object_id: usize) -> ObjectUpdate { self.state.full_update(object_id) }
+
+  // Pushes the player toward the target every tick, false if it didn't arrive in time
+  // or lost its mouse on the way (ie. the level restarted)
+  pub fn walk_to(&mut self, id: Uuid, target: IVec2, max_ticks: u32) -> bool {
+    let (mouse, loads) = (self.state.object_of(id), self.state.stats.level_loads);
+    for _ in 0..max_ticks {
+      if self.state.object_of(id) != mouse || self.state.stats.level_loads != loads { return false }
+      let Some(position) = self.position(id) else { return false };
+      let offset = target - position;
+      if arrived(offset) { return true }
+      self.push(id, steer(offset));
+      self.step(1);
+    }
+    false
+  }
+
+  // Json messages the player was sent since last asked, binary broadcasts aren't kept
+  pub fn messages(&mut self, id: Uuid) -> Vec<ServerMessage> {
+    let Some(inbox) = self.inboxes.get_mut(&id) else { return Vec::new() };
+    let mut messages = Vec::new();
+    while let Ok(event) = inbox.try_recv() {
+      let Event::Binary(_, Message::Text(text)) = event else { continue };
+      messages.push(serde_json::from_str(&text).unwrap());
+    }
+    messages
+  }
+
+  pub fn object_of(&self, id: Uuid) -> Option<usize> { self.state.object_of(id) }
+
+  // Player mice in the level, bots' and away players' included
+  pub fn mice(&self) -> usize { self.state.level.players.len() }
+
+  pub fn object_count(&self) -> usize { self.state.level.list.len() }
+
+  // Corners of the box around every level object
+  pub fn bounds(&self) -> (IVec2, IVec2) { self.state.level.bounds() }
+
+  pub fn position(&self, id: Uuid) -> Option<IVec2> {
+    let object_id = self.state.object_of(id)?;
+    Some(self.state.level.get_obj(object_id)?.position)
+  }
+
+  // Level objects get ids in the order they're listed in the level file
+  pub fn object_position(&self, object_id: usize) -> Option<IVec2> {
+    Some(self.state.level.get_obj(object_id)?.position)
+  }
+
+  pub fn material(&self, object_id: usize) -> Option<Material> {
+    Some(self.state.level.get_obj(object_id)?.material)
+  }
+
+  // Top left of every ping marker, in id order
+  pub fn pings(&self) -> Vec<IVec2> {
+    self.state.level.objects().into_iter()
+      .filter(|(_, object)| matches!(object.material, Material::Ping))
+      .map(|(_, object)| object.position)
+      .collect()
+  }
+
+  // Where every animated object is, in id order
+  pub fn animated_positions(&self) -> Vec<IVec2> {
+    let mut animated: Vec<usize> = self.state.level.animated.iter().copied().collect();
+    animated.sort();
+    animated.into_iter().map(|id| self.state.level.get_obj(id).unwrap().position).collect()
+  }
+
+  pub fn channel_held(&self, channel: u8) -> u8 { self.state.level.channels_held[channel as usize] }
+
+  // Players the channel's buttons need right now, after scaling
+  pub fn required(&self, channel: u8) -> u8 { self.state.level.button_requirements[channel as usize] }
+
+  pub fn channel_active(&self, channel: u8) -> bool {
+    self.channel_held(channel) >= self.state.level.button_requirements[channel as usize]
+  }
+
+  pub fn deaths(&self) -> u64 { self.state.stats.deaths }
+
+  pub fn wins(&self) -> u64 { self.state.stats.wins }
+
+  // Including restarts, whether from dying or failing
+  pub fn level_loads(&self) -> u64 { self.state.stats.level_loads }
+
+  pub fn collected(&self) -> u32 { self.state.level.total_collected }
+
+  pub fn collected_by(&self, id: Uuid) -> u32 {
+    self.state.object_of(id).map_or(0, |object_id| self.state.level.collected_by(object_id))
+  }
+
+  pub fn level(&self) -> &str { self.state.level.name() }
+}
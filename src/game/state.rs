@@ -1,22 +1,19 @@
-use super::{Level, Physics, Object};
-use std::collections::HashMap;
+use super::{Level, Physics, Object, Identity, ClientMessage, ServerMessage};
+use super::player::{Ghost, Ping, GRACE_PERIOD, MAX_PINGS, MAX_PLAYERS, PING_COOLDOWN, PING_LIFETIME};
+use super::chat::Chat;
+use super::rate_limit::RateLimit;
+use super::bots::{Bot, NavGrid, BOT_COLOR, BOT_NAME, REPLAN_TICKS, button_slots};
+use super::admin::{Command, DEFAULT_TICK_RATE, LOGIN_ATTEMPTS, LOGIN_WINDOW, MAX_TICK_RATE, MIN_TICK_RATE};
+use super::replay::{fnv, Input, Recorder, FNV_OFFSET};
+use std::{collections::{HashMap, HashSet, VecDeque}, time::Duration};
 use glam::IVec2;
 use uuid::Uuid;
 use axum::extract::ws::Message;
-use crate::{game::Material, networking::{Event, Server}};
-
-// If it really becomes a problem we can cut this down to bytes then reconstruct with ___views
-// Sent in u32 blocks
-// Size: bytes -- u32s
-#[repr(u8)]
-enum StateFlags {
-  Delete   = 0b00000001, // Size: 0
-  Position = 0b00000010, // Size: 8 -- 2
-  Shape    = 0b00000100, // Size: 4 + 8*length -- 1 + 2 * length
-  Material = 0b00001000, // Size: 4 -- 1
-  Hide     = 0b00010000, // Size: 0
-  Show     = 0b00100000, // Size: 0
-}
+use axum::http::StatusCode;
+use serde_json::json;
+use tracing::{info, warn};
+use crate::{api::{ApiRequest, ApiResponse}, game::Material, logging, metrics::GameStats, networking::{Event, Server}, protocol::{self, MessageKind, StateFlags, FEATURES, PROTOCOL_VERSION}};
+
 #[derive(Clone)]
 pub struct ObjectUpdate {
   position: Option<IVec2>,
@@ -24,10 +21,12 @@ pub struct ObjectUpdate {
   material: Option<Material>,
   hidden: bool,
   delete: bool,
+  name: Option<String>,
+  color: Option<u32>,
 }
 impl ObjectUpdate {
   pub fn new() -> Self {
-    Self { position: None, shape: None, material: None, hidden: false, delete: false }
+    Self { position: None, shape: None, material: None, hidden: false, delete: false, name: None, color: None }
   }
   pub fn delete(&mut self) -> &mut Self {
     self.delete = true;
@@ -49,13 +48,24 @@ impl ObjectUpdate {
     self.hidden = hidden;
     self
   }
+  pub fn color(&mut self, color: u32) -> &mut Self {
+    self.color = Some(color);
+    self
+  }
+  pub fn identity(&mut self, identity: &Identity) -> &mut Self {
+    self.name = Some(identity.name.clone());
+    self.color = Some(identity.color);
+    self
+  }
   pub fn to_binary(&self) -> Vec<i32> {
     let flag = 
       if self.position.is_some() { StateFlags::Position as i32 } else { 0 }      |
       if self.shape.is_some() { StateFlags::Shape as i32 } else { 0 }            | 
       if self.material.is_some() { StateFlags::Material as i32 } else { 0 }      |
       if self.hidden { StateFlags::Hide as i32 } else { StateFlags::Show as i32} |
-      if self.delete { StateFlags::Delete as i32 } else { 0 };
+      if self.delete { StateFlags::Delete as i32 } else { 0 }                    |
+      if self.name.is_some() { StateFlags::Name as i32 } else { 0 }              |
+      if self.color.is_some() { StateFlags::Color as i32 } else { 0 };
     let mut data = Vec::new();
     data.push(flag);
     if let Some(position) = self.position {
@@ -72,6 +82,13 @@ impl ObjectUpdate {
     if let Some(material) = self.material {
       data.push(material.color())
     }
+    if let Some(name) = &self.name {
+      data.push(name.chars().count() as i32);
+      data.extend(name.chars().map(|char| char as i32));
+    }
+    if let Some(color) = self.color {
+      data.push(color as i32);
+    }
     data
   }
 }
@@ -79,11 +96,31 @@ impl ObjectUpdate {
 pub struct GameState {
   // Pair connections to objects
   player_list: HashMap<Uuid, usize>,
-  pub level: Level,
+  identities: HashMap<Uuid, Identity>,
+  tokens: HashMap<Uuid, Uuid>, // Connection, resume token
+  ghosts: HashMap<Uuid, Ghost>, // Resume token, frozen mouse
+  spectators: HashSet<Uuid>,
+  chat: Chat,
+  pings: Vec<Ping>, // Oldest first
+  admins: HashSet<Uuid>,
+  failed_logins: RateLimit,
+  bots: Vec<Bot>,
+  bots_enabled: bool, // Fill in for missing players when there's at least one real one
+  pub(crate) admin_secret: Option<String>, // Remote admin is disabled without one
+  tick_rate: u32, // Ticks per second
+  frozen: bool,
+  pub(crate) stats: GameStats,
+  recorder: Option<Recorder>,
+  pub(crate) replayed_tokens: VecDeque<Uuid>, // Handed out instead of new ones while replaying
+  ticks: u64,
+  clock: Duration, // Game time, which runs at the same speed whatever the tick rate
+  uptime: Duration, // Like the clock, but keeps going while the game is frozen
+  pub(crate) level: Level,
   physics: Physics,
-  pub state_changes: HashMap<usize, ObjectUpdate>,
-  pub send_full: bool,
-  pub send_new: bool,
+  pub(crate) state_changes: HashMap<usize, ObjectUpdate>,
+  send_full: bool,
+  send_new: bool,
+  send_welcome: bool, // Object ids were reallocated, players need to hear their new one
 }
 impl GameState {
   
@@ -92,53 +129,219 @@ impl GameState {
     let level = Level::new(initial_level, &mut physics);
     Self {
       player_list: HashMap::new(),
+      identities: HashMap::new(),
+      tokens: HashMap::new(),
+      ghosts: HashMap::new(),
+      spectators: HashSet::new(),
+      chat: Chat::new(),
+      pings: Vec::new(),
+      admins: HashSet::new(),
+      failed_logins: RateLimit::new(LOGIN_ATTEMPTS, LOGIN_WINDOW),
+      bots: Vec::new(),
+      bots_enabled: false,
+      admin_secret: None,
+      tick_rate: DEFAULT_TICK_RATE,
+      frozen: false,
+      stats: GameStats::default(),
+      recorder: None,
+      replayed_tokens: VecDeque::new(),
+      ticks: 0,
+      clock: Duration::ZERO,
+      uptime: Duration::ZERO,
       level,
       physics,
       state_changes: HashMap::new(),
       send_full: false,
       send_new: true,
+      send_welcome: false,
     }
   }
 
-  pub fn load(&mut self, level: String) {
+  pub(crate) fn load(&mut self, level: String) {
+    info!(from = self.level.name(), to = level, "loading level");
     self.level = Level::new(level, &mut self.physics);
-    for (uuid, _) in &self.player_list.clone() {
-      let obj_id = self.level.add_object(Object::new_mouse(), Vec::new(), &mut self.physics, true);
-      self.player_list.insert(*uuid, obj_id);
+    self.stats.level_loads += 1;
+    self.pings.clear();
+    // Their mice went with the old level, they come back next tick
+    self.bots.clear();
+    // Sorted so replays hand out the same ids and teams
+    let mut players: Vec<Uuid> = self.player_list.keys().copied().collect();
+    players.sort();
+    for uuid in players {
+      let mouse = Object::new_mouse().on_team(self.level.next_team());
+      let obj_id = self.level.add_object(mouse, Vec::new(), &mut self.physics, true);
+      self.player_list.insert(uuid, obj_id);
+    }
+    let mut ghosts: Vec<&mut Ghost> = self.ghosts.values_mut().collect();
+    ghosts.sort_by_key(|ghost| ghost.object_id);
+    for ghost in ghosts {
+      let mouse = Object::new_mouse().on_team(self.level.next_team());
+      ghost.object_id = self.level.add_object(mouse, Vec::new(), &mut self.physics, true);
+      self.level.freeze_player(self.physics.body_sets().0, ghost.object_id, true);
     }
     self.send_new = true;
+    self.send_welcome = true;
+  }
+
+  // Remote admin stays disabled without one
+  pub fn set_admin_secret(&mut self, secret: Option<String>) { self.admin_secret = secret; }
+
+  // Everything from here on is saved for replay()
+  pub fn record(&mut self, path: &str) -> std::io::Result<()> {
+    self.recorder = Some(Recorder::new(path, self.level.name())?);
+    Ok(())
+  }
+
+  // Real time between ticks, and how far the game clock moves each one
+  pub fn tick_length(&self) -> Duration { Duration::from_secs(1) / self.tick_rate }
+
+  // The mouse a connection controls
+  pub fn object_of(&self, id: Uuid) -> Option<usize> { self.player_list.get(&id).copied() }
+
+  pub fn tick(&mut self) {
+    self.step();
+    if let Some(mut recorder) = self.recorder.take() {
+      recorder.finish_tick(self.checksum());
+      self.recorder = Some(recorder);
+    }
   }
 
-  pub fn tick(&mut self) { 
-    self.level.step_animations(&mut self.physics);
+  // Hash of every object's exact position, for catching replays that diverge
+  pub(crate) fn checksum(&self) -> u64 {
+    let mut ids: Vec<&usize> = self.level.list.keys().collect();
+    ids.sort();
+    ids.into_iter().fold(FNV_OFFSET, |hash, id| {
+      let body = self.physics.body(*self.level.list.get(id).unwrap()).unwrap();
+      let hash = fnv(hash, &id.to_le_bytes());
+      let hash = fnv(hash, &body.translation().x.to_le_bytes());
+      let hash = fnv(hash, &body.translation().y.to_le_bytes());
+      fnv(hash, &body.rotation().angle().to_le_bytes())
+    })
+  }
+
+  pub(crate) fn step(&mut self) { 
+    let tick_length = self.tick_length();
+    self.uptime += tick_length;
+    if self.frozen { return }
+    self.ticks += 1;
+    self.clock += tick_length;
+    self.expire_ghosts();
+    self.expire_pings();
+    self.update_bots();
+    let ticks = DEFAULT_TICK_RATE as f32 / self.tick_rate as f32;
+    self.level.step_animations(&mut self.physics, ticks);
     self.physics.step(&mut self.level);
-    if let Some(next_level) = self.level.tick(&mut self.physics, &mut self.state_changes) {
+    let next_level = self.level.tick(&mut self.physics, &mut self.state_changes, tick_length);
+    self.stats.deaths += std::mem::take(&mut self.level.deaths);
+    self.stats.wins += std::mem::take(&mut self.level.wins);
+    if let Some(next_level) = next_level {
       self.load(next_level);
     }
   }
 
+  #[tracing::instrument(level = "debug", skip_all)]
   pub fn handle_events(&mut self, server: &mut Server) {
     while let Ok(event) = server.mailbox.try_recv() {
-      match event {
-        Event::Connect(socket) => {
-          self.add_player(server.connect_socket(socket));
-          self.send_full = true;
-        },
-        Event::Disconnect(id) => { 
-          server.list.remove(&id);
-          let obj_id = self.player_list.remove(&id).unwrap();
-          self.level.delete(obj_id, &mut self.physics);
-          self.state_changes.entry(obj_id)
-            .or_insert(ObjectUpdate::new()).delete();
+      if let Event::Binary(..) = event { server.metrics.inbound_messages += 1; }
+      let input = match event {
+        Event::Connect(socket, spectate, version) => Input::Connect { id: server.connect_socket(socket), spectate, version },
+        Event::Disconnect(id) => Input::Disconnect(id),
+        Event::Command(command) => Input::Command(command),
+        Event::Api(request, response) => {
+          let _ = response.send(self.answer_api(server, request));
+          continue
         }
-        Event::Binary(id, message) => {
-          if let Message::Binary(bytes) = message {
-            let real_bytes = bytes.to_vec();
-            let data: &[i32] = bytemuck::cast_slice(&real_bytes);
-            self.update_player(id, IVec2::new(data[0], data[1]));
-          }
+        Event::Rtt(id, rtt) => {
+          server.rtt.insert(id, rtt);
+          continue
         }
+        Event::Binary(id, Message::Binary(bytes)) => match protocol::decode_movement(&bytes) {
+          Ok(delta) => Input::Move(id, delta),
+          Err(error) => { warn!(%id, error, "malformed movement message"); continue }
+        }
+        Event::Binary(id, Message::Text(text)) => Input::Text(id, text.to_string()),
+        Event::Binary(..) => continue,
+      };
+      self.apply(server, input);
+    }
+  }
+
+  // Recorded when recording, so replays can feed the same inputs back in
+  pub(crate) fn apply(&mut self, server: &mut Server, input: Input) {
+    // Logins are kept by outcome, so recordings never hold the secret
+    let input = match input {
+      Input::Text(id, text) => match serde_json::from_str(&text) {
+        Ok(ClientMessage::Admin { secret }) => {
+          // Once out of attempts even the right secret fails, so guessing tells nothing
+          let authenticated = !self.failed_logins.limited(id, self.uptime)
+            && self.admin_secret.as_ref() == Some(&secret);
+          if !authenticated { self.failed_logins.hit(id, self.uptime); }
+          Input::AdminLogin(id, authenticated)
+        }
+        _ => Input::Text(id, text),
+      }
+      input => input,
+    };
+    if let Some(recorder) = &mut self.recorder { recorder.input(&input); }
+    match input {
+      Input::Connect { id, spectate, version } => {
+        let Some(version) = protocol::negotiate(version) else {
+          warn!(%id, version, "rejected incompatible client");
+          let reason = format!("This server speaks protocol version {PROTOCOL_VERSION}, reload to update the game");
+          send_message(server, id, &ServerMessage::Rejected { reason });
+          // Dropping their sender closes the socket
+          server.list.remove(&id);
+          return
+        };
+        server.versions.insert(id, version);
+        let playing = !spectate && self.slot_free();
+        if playing { self.add_player(id); }
+        send_message(server, id, &ServerMessage::Hello {
+          version,
+          tick_rate: self.tick_rate,
+          object_id: self.player_list.get(&id).copied(),
+          features: FEATURES.iter().map(|feature| feature.to_string()).collect(),
+        });
+        if playing { self.start_session(server, id); }
+        else { self.add_spectator(server, id); }
+        for message in self.chat.history() { send_message(server, id, message); }
+        self.send_full = true;
+        self.level.status_changed = true;
+      },
+      Input::Disconnect(id) => { 
+        server.list.remove(&id);
+        server.rtt.remove(&id);
+        server.versions.remove(&id);
+        self.chat.forget(id);
+        self.failed_logins.forget(id);
+        self.admins.remove(&id);
+        if self.spectators.remove(&id) { return }
+        // Already removed if they were kicked
+        let Some(obj_id) = self.player_list.remove(&id) else { return };
+        info!(%id, object = obj_id, "player left, keeping their mouse for a while");
+        let identity = self.identities.remove(&id).unwrap();
+        let token = self.tokens.remove(&id).unwrap();
+        self.level.freeze_player(self.physics.body_sets().0, obj_id, true);
+        self.state_changes.entry(obj_id)
+          .or_insert(ObjectUpdate::new()).identity(&identity.away());
+        self.ghosts.insert(token, Ghost { object_id: obj_id, identity, expires: self.clock + GRACE_PERIOD });
       }
+      Input::Command(command) => {
+        // Run outside the macro, which skips its arguments when info is filtered out
+        let result = self.run_command(server, command);
+        info!("{result}");
+      }
+      Input::Move(id, delta) => self.update_player(id, delta),
+      Input::Text(id, text) => match serde_json::from_str(&text) {
+        Ok(message) => self.handle_message(server, id, message),
+        Err(error) => warn!(%id, %error, "malformed client message"),
+      }
+      Input::AdminLogin(id, authenticated) => {
+        if authenticated { info!(%id, "admin logged in") } else { warn!(%id, "failed admin login") }
+        if authenticated { self.admins.insert(id); }
+        send_message(server, id, &ServerMessage::Admin { authenticated });
+      }
+      Input::Token(_) => (),
     }
   }
 }
@@ -146,23 +349,400 @@ impl GameState {
 // Add Player, UpdatePlayer
 impl GameState {
   fn update_player(&mut self, id: Uuid, delta: IVec2) {
-    let handle = self.player_list.get(&id).unwrap();
+    // Pushes would pile up as velocity and fling everyone once the game unfreezes
+    if self.frozen { return }
+    let Some(handle) = self.player_list.get(&id) else { return };
     self.level.apply_vel(self.physics.body_sets().0, *handle, delta);
   }
 
   fn add_player(&mut self, connection_id: Uuid) {
     let object_id = self.level.add_object(
-      Object::new_mouse(),
+      Object::new_mouse().on_team(self.level.next_team()),
       Vec::new(), &mut self.physics, true
     );
     self.player_list.insert(connection_id, object_id);
+    let identity = Identity::new("", None, &self.taken_colors(connection_id));
+    self.identities.insert(connection_id, identity);
+    self.state_changes.insert(object_id, self.full_update(object_id));
+  }
+
+  fn new_token(&mut self) -> Uuid {
+    let token = self.replayed_tokens.pop_front().unwrap_or_else(Uuid::new_v4);
+    if let Some(recorder) = &mut self.recorder { recorder.input(&Input::Token(token)); }
+    token
+  }
+
+  fn slot_free(&self) -> bool { self.player_list.len() + self.ghosts.len() < MAX_PLAYERS }
+
+  fn join_game(&mut self, server: &mut Server, id: Uuid) {
+    self.add_player(id);
+    self.start_session(server, id);
+    send_message(server, id, &ServerMessage::Welcome { object_id: self.player_list[&id] });
+  }
+
+  fn start_session(&mut self, server: &mut Server, id: Uuid) {
+    let token = self.new_token();
+    self.tokens.insert(id, token);
+    send_message(server, id, &ServerMessage::Session { token });
+    send_message(server, id, &ServerMessage::Role { spectating: false });
+  }
+
+  fn add_spectator(&mut self, server: &mut Server, id: Uuid) {
+    self.spectators.insert(id);
+    send_message(server, id, &ServerMessage::Role { spectating: true });
+  }
+
+  fn handle_message(&mut self, server: &mut Server, id: Uuid, message: ClientMessage) {
+    match message {
+      ClientMessage::Chat { text } => {
+        let (name, color) = match self.identities.get(&id) {
+          Some(identity) => (identity.name.clone(), Some(identity.color)),
+          None => ("Spectator".to_owned(), None),
+        };
+        let Some(message) = self.chat.accept(id, name, color, &text, self.uptime) else { return };
+        let connections: Vec<Uuid> = server.list.keys().copied().collect();
+        for connection in connections { send_message(server, connection, &message); }
+      }
+      ClientMessage::Ping { position } => {
+        let Some(object_id) = self.player_list.get(&id) else { return };
+        if self.pings.iter().any(|ping| ping.owner == id && self.clock < ping.placed + PING_COOLDOWN) { return }
+        // Clients pick the spot, so keep it somewhere that exists
+        let (min, max) = self.level.bounds();
+        let position = position.unwrap_or(self.level.get_obj(*object_id).unwrap().position).clamp(min, max);
+        let owned = self.pings.iter().filter(|ping| ping.owner == id).count();
+        if owned >= MAX_PINGS {
+          let oldest = self.pings.iter().position(|ping| ping.owner == id).unwrap();
+          self.remove_ping(oldest);
+        }
+        let object_id = self.level.add_object(Object::new_ping(position), Vec::new(), &mut self.physics, false);
+        self.pings.push(Ping { object_id, owner: id, placed: self.clock, expires: self.clock + PING_LIFETIME });
+        self.state_changes.insert(object_id, self.full_update(object_id));
+      }
+      ClientMessage::Play => {
+        if !self.slot_free() || !self.spectators.remove(&id) { return }
+        self.join_game(server, id);
+      }
+      ClientMessage::Spectate => {
+        if self.remove_player(id) { self.add_spectator(server, id); }
+      }
+      // Already turned into Input::AdminLogin by apply()
+      ClientMessage::Admin { .. } => (),
+      ClientMessage::Command { command } => {
+        if !self.admins.contains(&id) { return }
+        info!(%id, command, "admin command");
+        let text = match Command::parse(&command) {
+          Ok(command) => self.run_command(server, command),
+          Err(usage) => usage,
+        };
+        send_message(server, id, &ServerMessage::Notice { text });
+      }
+      ClientMessage::Resume { token } => {
+        let Some(ghost) = self.ghosts.remove(&token) else { return };
+        // The ghost held a slot, so a full server may have made us spectate
+        if self.spectators.remove(&id) {
+          send_message(server, id, &ServerMessage::Role { spectating: false });
+        }
+        // Drop the mouse this connection was given before it resumed
+        if let Some(object_id) = self.player_list.insert(id, ghost.object_id) {
+          self.level.delete(object_id, &mut self.physics);
+          self.state_changes.entry(object_id)
+            .or_insert(ObjectUpdate::new()).delete();
+        }
+        self.level.freeze_player(self.physics.body_sets().0, ghost.object_id, false);
+        self.state_changes.entry(ghost.object_id)
+          .or_insert(ObjectUpdate::new()).identity(&ghost.identity);
+        info!(%id, name = ghost.identity.name, "player resumed");
+        self.identities.insert(id, ghost.identity);
+        self.tokens.insert(id, token);
+        send_message(server, id, &ServerMessage::Session { token });
+        send_message(server, id, &ServerMessage::Welcome { object_id: ghost.object_id });
+      }
+      ClientMessage::Join { name, color } => {
+        let identity = Identity::new(&name, color, &self.taken_colors(id));
+        let Some(object_id) = self.player_list.get(&id) else { return };
+        self.state_changes.entry(*object_id)
+          .or_insert(ObjectUpdate::new()).identity(&identity);
+        self.identities.insert(id, identity);
+      }
+    }
+  }
+
+  fn taken_colors(&self, except: Uuid) -> Vec<u32> {
+    self.identities.iter()
+      .filter(|(id, _)| **id != except)
+      .map(|(_, identity)| identity.color)
+      .chain(self.ghosts.values().map(|ghost| ghost.identity.color))
+      .collect()
+  }
+
+  // Whether the connection had a mouse to remove
+  fn remove_player(&mut self, id: Uuid) -> bool {
+    let Some(object_id) = self.player_list.remove(&id) else { return false };
+    self.identities.remove(&id);
+    self.tokens.remove(&id);
+    self.level.delete(object_id, &mut self.physics);
+    self.state_changes.entry(object_id)
+      .or_insert(ObjectUpdate::new()).delete();
+    true
+  }
+
+  // Describes what happened for whoever issued the command
+  pub(crate) fn run_command(&mut self, server: &mut Server, command: Command) -> String {
+    match command {
+      Command::Restart => {
+        let level = self.level.name().to_owned();
+        self.load(level.clone());
+        format!("Restarted {level}")
+      }
+      Command::Load(level) => {
+        if !Level::exists(&level) { return format!("No level named {level}") }
+        self.load(level.clone());
+        format!("Loaded {level}")
+      }
+      Command::Kick(player) => {
+        // Names aren't unique (everyone who skips the nickname is Mouse), so only kick a clear match
+        let matching: Vec<Uuid> = self.player_list.iter()
+          .filter(|(id, object_id)| {
+            id.to_string() == player || object_id.to_string() == player || self.identities[*id].name == player
+          })
+          .map(|(id, _)| *id).collect();
+        let id = match matching[..] {
+          [id] => id,
+          [] => return format!("No player {player}"),
+          _ => return format!("{} players match {player}, kick by mouse id instead", matching.len()),
+        };
+        let name = self.identities[&id].name.clone();
+        self.remove_player(id);
+        // Rejected rather than just closed, so the client doesn't reconnect on its own
+        send_message(server, id, &ServerMessage::Rejected { reason: "You were kicked by an admin".to_owned() });
+        // Dropping their sender closes the socket
+        server.list.remove(&id);
+        format!("Kicked {name}")
+      }
+      Command::Freeze(frozen) => {
+        self.frozen = frozen;
+        if frozen { "Game frozen".to_owned() } else { "Game unfrozen".to_owned() }
+      }
+      Command::LogLevel(directives) => match logging::set_filter(&directives) {
+        Ok(()) => format!("Log filter set to {directives}"),
+        Err(error) => format!("Bad log filter: {error}"),
+      }
+      Command::Bots(enabled) => {
+        self.bots_enabled = enabled;
+        if enabled { "Bots will fill in for missing players".to_owned() } else { "Bots disabled".to_owned() }
+      }
+      Command::TickRate(rate) => {
+        self.tick_rate = rate.clamp(MIN_TICK_RATE, MAX_TICK_RATE);
+        self.physics.set_tick_rate(self.tick_rate);
+        format!("Tick rate set to {}", self.tick_rate)
+      }
+    }
+  }
+
+  fn answer_api(&mut self, server: &mut Server, request: ApiRequest) -> ApiResponse {
+    match request {
+      ApiRequest::Status => (StatusCode::OK, json!({
+        "level": self.level.name(),
+        "tick_rate": self.tick_rate,
+        "uptime": server.started.elapsed().as_secs(),
+        "players": self.player_list.len(),
+        "spectators": self.spectators.len(),
+        "frozen": self.frozen,
+      })),
+      ApiRequest::Players => {
+        let players: Vec<_> = self.player_list.iter().map(|(id, object_id)| {
+          let identity = self.identities.get(id).unwrap();
+          let position = self.level.get_obj(*object_id).unwrap().position;
+          json!({
+            "id": id,
+            "object_id": object_id,
+            "name": identity.name,
+            "color": identity.color,
+            "rtt": server.rtt.get(id).map(|rtt| rtt.as_millis() as u64),
+            "position": position,
+            "collected": self.level.collected_by(*object_id),
+          })
+        }).collect();
+        (StatusCode::OK, json!(players))
+      }
+      ApiRequest::Metrics => {
+        let text = server.metrics.render(&self.stats, self.player_list.len(), self.spectators.len());
+        (StatusCode::OK, json!(text))
+      }
+      ApiRequest::Load { level, secret } => {
+        let authorized = self.admin_secret.is_some() && self.admin_secret == secret;
+        if !authorized { return (StatusCode::UNAUTHORIZED, json!({ "error": "bad admin secret" })) }
+        if !Level::exists(&level) { return (StatusCode::NOT_FOUND, json!({ "error": format!("no level named {level}") })) }
+        self.apply(server, Input::Command(Command::Load(level)));
+        (StatusCode::OK, json!({ "level": self.level.name() }))
+      }
+    }
+  }
+
+  // Adds or removes bots until the level has enough players, then walks them to their buttons
+  fn update_bots(&mut self) {
+    let humans = self.player_list.len();
+    let free = MAX_PLAYERS.saturating_sub(humans + self.ghosts.len());
+    // Humans can't hold buttons while they walk to the exit, so bots hold all of them then
+    let holders = if self.level.needs_exit() { 0 } else { humans };
+    let wanted = if !self.bots_enabled || humans == 0 { 0 } else {
+      (0..=free).find(|bots| holders + bots >= self.level.players_needed(humans + bots)).unwrap_or(free)
+    };
+    while self.bots.len() > wanted {
+      let bot = self.bots.pop().unwrap();
+      self.level.delete(bot.object_id, &mut self.physics);
+      self.state_changes.entry(bot.object_id)
+        .or_insert(ObjectUpdate::new()).delete();
+    }
+    let grid = (self.bots.len() < wanted).then(|| NavGrid::new(&self.level));
+    while self.bots.len() < wanted {
+      // Beside everyone else rather than inside them, overlapping mice jam together
+      let mice: Vec<IVec2> = self.level.players.iter().map(|id| self.level.get_obj(*id).unwrap().position).collect();
+      let spawn = grid.as_ref().unwrap().free_spot(IVec2::ZERO, &mice);
+      let mouse = Object::new_mouse().on_team(self.level.next_team()).at(spawn);
+      let object_id = self.level.add_object(mouse, Vec::new(), &mut self.physics, true);
+      self.level.bots.insert(object_id);
+      self.bots.push(Bot::new(object_id, self.bots.len()));
+      self.state_changes.insert(object_id, self.full_update(object_id));
+    }
+    if self.bots.is_empty() { return }
+
+    let replan = self.ticks.is_multiple_of(REPLAN_TICKS) || self.bots.iter().any(|bot| !bot.planned);
+    let plan = replan.then(|| (NavGrid::new(&self.level), button_slots(&self.level)));
+    for bot in &mut self.bots {
+      let position = self.level.get_obj(bot.object_id).unwrap().position;
+      if let Some((grid, slots)) = &plan {
+        bot.path = slots.get(bot.slot).and_then(|slot| grid.path(position, *slot)).unwrap_or_default();
+        bot.planned = true;
+      }
+      let Some(delta) = bot.next_move(position) else { continue };
+      self.level.apply_vel(self.physics.body_sets().0, bot.object_id, delta);
+    }
+  }
+
+  fn expire_pings(&mut self) {
+    while self.pings.first().is_some_and(|ping| ping.expires <= self.clock) { self.remove_ping(0); }
+  }
+
+  fn remove_ping(&mut self, index: usize) {
+    let ping = self.pings.remove(index);
+    self.level.delete(ping.object_id, &mut self.physics);
+    self.state_changes.entry(ping.object_id)
+      .or_insert(ObjectUpdate::new()).delete();
+  }
+
+  fn expire_ghosts(&mut self) {
+    let clock = self.clock;
+    let mut expired: Vec<(usize, Uuid)> = self.ghosts.iter()
+      .filter(|(_, ghost)| ghost.expires <= clock)
+      .map(|(token, ghost)| (ghost.object_id, *token)).collect();
+    // Freed ids get handed out again, so replays have to free them in the same order
+    expired.sort();
+    for (_, token) in expired {
+      let ghost = self.ghosts.remove(&token).unwrap();
+      info!(name = ghost.identity.name, "resume grace period expired");
+      self.level.delete(ghost.object_id, &mut self.physics);
+      self.state_changes.entry(ghost.object_id)
+        .or_insert(ObjectUpdate::new()).delete();
+    }
+  }
+
+  // Everything a client needs to draw the object from scratch
+  pub fn full_update(&self, object_id: usize) -> ObjectUpdate {
     let object = self.level.get_obj(object_id).unwrap();
-    let update = ObjectUpdate::new()
-      .position(object.position)
-      .shape(object.points.clone())
-      .material(object.material)
-      .clone();
-    self.state_changes.insert(object_id, update);
+    let mut update = ObjectUpdate::new();
+    update.position(object.position)
+      .shape(object.shape())
+      .material(object.material);
+    let owner = self.player_list.iter().find(|(_, id)| **id == object_id);
+    if let Some(identity) = owner.and_then(|(uuid, _)| self.identities.get(uuid)) {
+      update.identity(identity);
+    }
+    if let Some(ghost) = self.ghosts.values().find(|ghost| ghost.object_id == object_id) {
+      update.identity(&ghost.identity.away());
+    }
+    if self.bots.iter().any(|bot| bot.object_id == object_id) {
+      update.identity(&Identity { name: BOT_NAME.to_owned(), color: BOT_COLOR });
+    }
+    let pinger = self.pings.iter().find(|ping| ping.object_id == object_id);
+    if let Some(identity) = pinger.and_then(|ping| self.identities.get(&ping.owner)) {
+      update.color(identity.color);
+    }
+    update
   }
 }
 
+
+// Binary messages broadcast to every client after a tick
+impl GameState {
+  // Tells every player their mouse after a level load, before the state that introduces it
+  pub fn send_welcomes(&mut self, server: &mut Server) {
+    if !self.send_welcome { return }
+    self.send_welcome = false;
+    for (id, object_id) in &self.player_list {
+      send_message(server, *id, &ServerMessage::Welcome { object_id: *object_id });
+    }
+  }
+
+  // [kind, count, (length, id, update)*], None if nothing changed
+  #[tracing::instrument(level = "debug", skip_all)]
+  pub fn state_message(&mut self) -> Option<Vec<i32>> {
+    let mut message_data = vec![MessageKind::State as i32, 0];
+    if self.send_full || self.send_new {
+      for id in self.level.list.keys() {
+        let mut update_data = self.full_update(*id).to_binary();
+        message_data.push(update_data.len() as i32 + 1);
+        message_data.push(*id as i32);
+        message_data.append(&mut update_data);
+        message_data[1] += 1;
+      }
+      self.send_full = false;
+    } else if self.state_changes.is_empty() { return None } 
+    if self.send_new { 
+      message_data[1] *= -1;
+      self.send_new = false;
+      self.state_changes.clear();
+    } else {
+      for (id, update) in self.state_changes.drain() {
+        let mut update_data = update.to_binary();
+        message_data.push(update_data.len() as i32 + 1);
+        message_data.push(id as i32);
+        message_data.append(&mut update_data);
+        message_data[1] += 1;
+      }
+    }
+    Some(message_data)
+  }
+
+  // [kind, players, collected, in_exit, count, (channel, held, required)*, collect_goal, time_left],
+  // None if unchanged. No goal is 0 and no time limit -1
+  pub fn channels_message(&mut self) -> Option<Vec<i32>> {
+    let level = &mut self.level;
+    if !level.status_changed { return None }
+    level.status_changed = false;
+    let mut message_data = vec![
+      MessageKind::Channels as i32,
+      level.players.len() as i32,
+      level.total_collected as i32,
+      level.players_in_exit() as i32,
+      level.channels.len() as i32,
+    ];
+    for channel in &level.channels {
+      let channel = *channel as usize;
+      message_data.push(channel as i32);
+      message_data.push(level.channels_held[channel] as i32);
+      message_data.push(level.button_requirements[channel] as i32);
+    }
+    message_data.push(level.collect_goal().unwrap_or(0) as i32);
+    message_data.push(level.time_left().map_or(-1, |seconds| seconds as i32));
+    Some(message_data)
+  }
+}
+
+
+fn send_message(server: &mut Server, id: Uuid, message: &ServerMessage) {
+  let Some(connection) = server.list.get(&id) else { return };
+  let text = serde_json::to_string(message).unwrap();
+  server.metrics.record_send(text.len());
+  let _ = connection.send(Event::Binary(id, Message::Text(text.into())));
+}
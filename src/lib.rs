@@ -0,0 +1,13 @@
+// The game core, its wire protocol and the server it runs behind
+// Argument-less constructors are spelled new() throughout, without Default impls
+#![allow(clippy::new_without_default)]
+pub mod game;
+pub mod networking;
+pub mod api;
+pub mod metrics;
+pub mod logging;
+pub mod protocol;
+
+pub use game::{GameState, Level, ObjectUpdate, Simulation};
+pub use protocol::{Broadcast, MessageKind, Update};
+pub use networking::{Event, Server};
@@ -0,0 +1,19 @@
+use std::sync::OnceLock;
+use tracing_subscriber::{fmt, prelude::*, reload, EnvFilter, Registry};
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+// Filter comes from RUST_LOG (ie. "info,mouse_game=debug"), defaulting to info
+pub fn init() {
+  let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+  let (filter, handle) = reload::Layer::new(filter);
+  tracing_subscriber::registry().with(filter).with(fmt::layer()).init();
+  let _ = FILTER.set(handle);
+}
+
+// Swaps the filter on the running subscriber
+pub fn set_filter(directives: &str) -> Result<(), String> {
+  let filter = EnvFilter::try_new(directives).map_err(|error| error.to_string())?;
+  let handle = FILTER.get().ok_or("logging isn't initialized")?;
+  handle.reload(filter).map_err(|error| error.to_string())
+}
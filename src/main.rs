@@ -1,66 +1,48 @@
-mod networking;
-mod game;
-use std::{net::SocketAddr, thread::sleep, time::{Duration, Instant}};
-use axum::extract::ws::Message;
-use networking::{Server, Event};
-use uuid::Uuid;
-use crate::game::{GameState, ObjectUpdate};
-const SERVER_UUID: Uuid = Uuid::nil();
+use std::{net::SocketAddr, thread::sleep, time::Instant};
+use mouse_game::{logging, Event, GameState, Server};
+use mouse_game::game::{replay, spawn_console, Command};
 
 #[tokio::main]
 async fn main() {
+  logging::init();
+  // --record <file> saves every input, --replay <file> plays a recording back without networking
+  let args: Vec<String> = std::env::args().collect();
+  let flag = |name: &str| args.iter().position(|arg| arg == name).and_then(|idx| args.get(idx + 1));
+  let admin_secret = std::env::var("MOUSE_ADMIN_SECRET").ok().filter(|secret| !secret.is_empty());
+  // Bots stay off unless MOUSE_BOTS=on, admins can still switch them with "/bots on"
+  let bots = std::env::var("MOUSE_BOTS").is_ok_and(|bots| bots == "on");
+  if let Some(path) = flag("--replay") {
+    match replay(path) {
+      Ok(None) => println!("Replay matched the recording"),
+      Ok(Some(tick)) => println!("Replay diverged at tick {tick}"),
+      Err(error) => println!("Couldn't replay {path}: {error}"),
+    }
+    return
+  }
+
   let mut server = Server::new(SocketAddr::from(([0, 0, 0, 0], 8080)));
   let mut game_state = GameState::new("level1".to_owned());
-  let update_interval = Duration::from_millis(20); // 20 updates per second
+  game_state.set_admin_secret(admin_secret);
+  if let Some(path) = flag("--record") {
+    game_state.record(path).expect("couldn't create replay file");
+  }
+  // Sent like a console command, so recordings start with the same bots
+  if bots { let _ = server.tx.send(Event::Command(Command::Bots(true))); }
+  spawn_console(server.tx.clone());
   let mut last_update = Instant::now();
   loop {
-    let now = Instant::now();
-    let elapsed = now.duration_since(last_update);
+    let update_interval = game_state.tick_length();
+    let elapsed = last_update.elapsed();
     if elapsed < update_interval { sleep(update_interval - elapsed); }
-    last_update = now;
+    last_update = Instant::now();
 
+    let _tick = tracing::debug_span!("tick").entered();
     game_state.handle_events(&mut server);
     game_state.tick();
 
-    broadcast_state(&mut game_state, &mut server);
+    game_state.send_welcomes(&mut server);
+    if let Some(message) = game_state.channels_message() { server.send_all(&message); }
+    if let Some(message) = game_state.state_message() { server.send_all(&message); }
+    server.metrics.record_tick(last_update.elapsed(), update_interval);
   }
 }
-
-// Update consists of [i32_count, id, data]
-fn broadcast_state(state: &mut GameState, server: &mut Server) {
-  let mut message_data = Vec::new();
-  message_data.push(0);
-  if state.send_full || state.send_new {
-    for id in state.level.list.keys() {
-      let obj = state.level.get_obj(*id).unwrap();
-      let mut update_data = ObjectUpdate::new()
-        .position(obj.position)
-        .shape(obj.points.clone())
-        .material(obj.material)
-        .to_binary();
-      message_data.push(update_data.len() as i32 + 1);
-      message_data.push(*id as i32);
-      message_data.append(&mut update_data);
-      message_data[0] += 1;
-    }
-    state.send_full = false;
-  } else if state.state_changes.len() == 0 { return } 
-  if state.send_new { 
-    message_data[0] *= -1;
-    state.send_new = false;
-    state.state_changes.clear();
-  } else {
-    for (id, update) in state.state_changes.drain() {
-      let mut update_data = update.to_binary();
-      message_data.push(update_data.len() as i32 + 1);
-      message_data.push(id as i32);
-      message_data.append(&mut update_data);
-      message_data[0] += 1;
-    }
-  }
-  let message = Message::Binary(bytemuck::cast_slice(&message_data).to_vec().into());
-  for (_, connection) in &server.list {
-    let _ = connection.send(Event::Binary(SERVER_UUID, message.clone()));
-  }
-}
-
@@ -0,0 +1,78 @@
+use std::{fmt::Write, time::Duration};
+
+// Upper bounds in seconds, Prometheus adds the +Inf bucket itself from the count
+const TICK_BUCKETS: [f64; 8] = [0.001, 0.002, 0.005, 0.01, 0.02, 0.05, 0.1, 0.25];
+
+// Networking and loop timing, kept by the Server
+#[derive(Default)]
+pub struct Metrics {
+  tick_buckets: [u64; TICK_BUCKETS.len()],
+  tick_count: u64,
+  tick_seconds: f64,
+  pub tick_overruns: u64,
+  pub bytes_sent: u64,
+  pub messages_sent: u64,
+  pub tick_bytes_sent: u64, // Since the start of the current tick
+  pub tick_messages_sent: u64,
+  last_tick_bytes_sent: u64,
+  last_tick_messages_sent: u64,
+  pub inbound_messages: u64,
+}
+impl Metrics {
+  pub fn record_tick(&mut self, duration: Duration, budget: Duration) {
+    let seconds = duration.as_secs_f64();
+    for (bucket, bound) in self.tick_buckets.iter_mut().zip(TICK_BUCKETS) {
+      if seconds <= bound { *bucket += 1; }
+    }
+    self.tick_count += 1;
+    self.tick_seconds += seconds;
+    if duration > budget { self.tick_overruns += 1; }
+    self.last_tick_bytes_sent = std::mem::take(&mut self.tick_bytes_sent);
+    self.last_tick_messages_sent = std::mem::take(&mut self.tick_messages_sent);
+  }
+
+  pub fn record_send(&mut self, bytes: usize) {
+    self.bytes_sent += bytes as u64;
+    self.messages_sent += 1;
+    self.tick_bytes_sent += bytes as u64;
+    self.tick_messages_sent += 1;
+  }
+
+  // Prometheus text exposition format
+  pub fn render(&self, stats: &GameStats, players: usize, spectators: usize) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# TYPE mouse_tick_duration_seconds histogram");
+    for (bucket, bound) in self.tick_buckets.iter().zip(TICK_BUCKETS) {
+      let _ = writeln!(out, "mouse_tick_duration_seconds_bucket{{le=\"{bound}\"}} {bucket}");
+    }
+    let _ = writeln!(out, "mouse_tick_duration_seconds_bucket{{le=\"+Inf\"}} {}", self.tick_count);
+    let _ = writeln!(out, "mouse_tick_duration_seconds_sum {}", self.tick_seconds);
+    let _ = writeln!(out, "mouse_tick_duration_seconds_count {}", self.tick_count);
+    let values = [
+      ("mouse_tick_overruns_total", "counter", self.tick_overruns),
+      ("mouse_players", "gauge", players as u64),
+      ("mouse_spectators", "gauge", spectators as u64),
+      ("mouse_bytes_sent_total", "counter", self.bytes_sent),
+      ("mouse_messages_sent_total", "counter", self.messages_sent),
+      ("mouse_last_tick_bytes_sent", "gauge", self.last_tick_bytes_sent),
+      ("mouse_last_tick_messages_sent", "gauge", self.last_tick_messages_sent),
+      ("mouse_inbound_messages_total", "counter", self.inbound_messages),
+      ("mouse_level_loads_total", "counter", stats.level_loads),
+      ("mouse_deaths_total", "counter", stats.deaths),
+      ("mouse_wins_total", "counter", stats.wins),
+    ];
+    for (name, kind, value) in values {
+      let _ = writeln!(out, "# TYPE {name} {kind}");
+      let _ = writeln!(out, "{name} {value}");
+    }
+    out
+  }
+}
+
+// Gameplay counters, kept by the GameState across level loads
+#[derive(Default)]
+pub struct GameStats {
+  pub level_loads: u64,
+  pub deaths: u64,
+  pub wins: u64,
+}
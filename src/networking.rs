@@ -1,65 +1,119 @@
-use axum::extract::ws::{Message, WebSocket};
+use axum::{body::Bytes, extract::ws::{Message, WebSocket}};
 use futures::{StreamExt, SinkExt};
-use std::{collections::hash_map::HashMap, net::SocketAddr};
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
-use axum::{ extract::{ws::WebSocketUpgrade, State}, Router};
+use std::{collections::hash_map::HashMap, net::SocketAddr, time::{Duration, Instant}};
+use tokio::sync::{mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender}, oneshot};
+use axum::{ extract::{ws::WebSocketUpgrade, Query, State}, Router};
 use tower_http::services::ServeDir;
 use tokio::net::TcpListener;
 use uuid::Uuid;
+use crate::{api::{self, ApiRequest, ApiResponse}, game::Command, metrics::Metrics, protocol::{Broadcast, PROTOCOL_VERSION}};
+
+const PING_INTERVAL: Duration = Duration::from_secs(2);
+const SERVER_UUID: Uuid = Uuid::nil();
 
 pub enum Event {
-  Connect(WebSocket),
+  Connect(Box<WebSocket>, bool, u32), // Socket, spectating, announced protocol version
   Binary(Uuid, Message),
   Disconnect(Uuid),
+  Command(Command), // From the server console
+  Api(ApiRequest, oneshot::Sender<ApiResponse>),
+  Rtt(Uuid, Duration),
 }
 
 pub struct Server {
   pub mailbox: UnboundedReceiver<Event>,
   pub tx: UnboundedSender<Event>,
-  pub list: HashMap<Uuid, UnboundedSender<Event>>
+  pub list: HashMap<Uuid, UnboundedSender<Event>>,
+  pub rtt: HashMap<Uuid, Duration>, // Latest round trip per connection
+  pub versions: HashMap<Uuid, u32>, // Negotiated protocol version per connection
+  pub started: Instant,
+  pub metrics: Metrics,
 }
 impl Server {
   pub fn new(address: SocketAddr) -> Self {
     let (tx, mailbox) = unbounded_channel();
     
     let app = Router::new().route("/ws", axum::routing::get(
-      |ws: WebSocketUpgrade, State(svr_tx): State<UnboundedSender<Event>>| async move {
+      |ws: WebSocketUpgrade, Query(params): Query<HashMap<String, String>>, State(svr_tx): State<UnboundedSender<Event>>| async move {
+        let spectate = params.contains_key("spectate");
+        let version = params.get("version").and_then(|version| version.parse().ok()).unwrap_or(0);
         ws.on_upgrade(move |socket| async move { 
-          let _ = svr_tx.send(Event::Connect(socket));
+          let _ = svr_tx.send(Event::Connect(Box::new(socket), spectate, version));
         })
-    })).with_state(tx.clone()).fallback_service(ServeDir::new("web"));
+    })).merge(api::routes()).with_state(tx.clone()).fallback_service(ServeDir::new("web"));
     
     tokio::spawn(async move {
       axum::serve(TcpListener::bind(address).await.unwrap(), app).await.unwrap();
     });
-    println!("Running at http://{}", address);
-    Self { mailbox, tx, list: HashMap::new() }
+    tracing::info!("Running at http://{}", address);
+    Self { mailbox, tx, list: HashMap::new(), rtt: HashMap::new(), versions: HashMap::new(), started: Instant::now(), metrics: Metrics::default() }
   }
   
-  pub fn connect_socket(&mut self, socket: WebSocket) -> Uuid {
+  // No listener, for driving a GameState without any clients (ie. replays)
+  pub fn offline() -> Self {
+    let (tx, mailbox) = unbounded_channel();
+    Self { mailbox, tx, list: HashMap::new(), rtt: HashMap::new(), versions: HashMap::new(), started: Instant::now(), metrics: Metrics::default() }
+  }
+
+  pub fn connect_socket(&mut self, socket: Box<WebSocket>) -> Uuid {
     let id = Uuid::new_v4();
     let (client_tx, client_mailbox) = unbounded_channel::<Event>();
     let server_tx = self.tx.clone();
-    tokio::spawn(async move { handle_socket(socket, id, server_tx, client_mailbox).await });
+    tokio::spawn(async move { handle_socket(*socket, id, server_tx, client_mailbox).await });
     self.list.insert(id, client_tx);
+    tracing::info!(%id, "connected");
     id
   }
 
+  // Encoded once per protocol version in use, not once per connection
+  pub fn send_all(&mut self, message_data: &[i32]) {
+    let mut payloads: HashMap<u32, Bytes> = HashMap::new();
+    for (id, connection) in &self.list {
+      let version = self.versions.get(id).copied().unwrap_or(PROTOCOL_VERSION);
+      // Only ever given broadcasts the game built, so they always decode
+      let bytes = payloads.entry(version)
+        .or_insert_with(|| Broadcast::payload(message_data, version).unwrap().into())
+        .clone();
+      self.metrics.record_send(bytes.len());
+      let _ = connection.send(Event::Binary(SERVER_UUID, Message::Binary(bytes)));
+    }
+  }
 }
 
 async fn handle_socket(socket: WebSocket, id: Uuid, server_tx: UnboundedSender<Event>, mut mailbox: UnboundedReceiver<Event>) {
   let (mut sender, mut receiver) = socket.split();
   
+  // Pings carry the millis since connecting, so the pong tells us the round trip
+  let connected = Instant::now();
   let ws_output = tokio::spawn(async move {
-    while let Some(Event::Binary(_, msg)) = mailbox.recv().await {
+    let mut pinger = tokio::time::interval(PING_INTERVAL);
+    loop {
+      let msg = tokio::select! {
+        event = mailbox.recv() => match event {
+          Some(Event::Binary(_, msg)) => msg,
+          _ => break,
+        },
+        _ = pinger.tick() => {
+          let sent = connected.elapsed().as_millis() as u64;
+          Message::Ping(sent.to_le_bytes().to_vec().into())
+        }
+      };
       if sender.send(msg).await.is_err() { break; }
     }
+    // The server dropped us (kicked, rejected), the reading half alone would keep the socket open
+    let _ = sender.send(Message::Close(None)).await;
   });
 
   let ws_input = {
     let tx = server_tx.clone();
     tokio::spawn(async move {
       while let Some(Ok(msg)) = receiver.next().await {
+        if let Message::Pong(payload) = &msg {
+          let Ok(sent) = payload.as_ref().try_into().map(u64::from_le_bytes) else { continue };
+          let rtt = connected.elapsed().saturating_sub(Duration::from_millis(sent));
+          tx.send(Event::Rtt(id, rtt)).unwrap();
+          continue;
+        }
         tx.send(Event::Binary(id, msg)).unwrap();
       }
     })
@@ -69,6 +123,7 @@ async fn handle_socket(socket: WebSocket, id: Uuid, server_tx: UnboundedSender<E
     _ = ws_output => (),
     _ = ws_input => (),
   }
+  tracing::info!(%id, "disconnected");
   server_tx.send(Event::Disconnect(id)).unwrap();
 }
 
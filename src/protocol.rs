@@ -0,0 +1,381 @@
+use glam::IVec2;
+// Text messages are plain json in both directions
+pub use crate::game::{ClientMessage, ServerMessage};
+
+// Bumped whenever the binary layout or the json messages change incompatibly
+pub const PROTOCOL_VERSION: u32 = 2;
+// Clients from this version on get broadcasts in the compact byte encoding instead of i32 words
+pub const COMPACT_VERSION: u32 = 2;
+// Oldest version the server can still talk, clients that didn't announce one are version 0
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+// Optional parts of the game this server has, announced in the hello
+pub const FEATURES: &[&str] = &["chat", "pings", "spectate", "resume", "admin", "bots"];
+
+// Newer clients are talked to in our version and told so, older ones get theirs if we still speak it
+pub fn negotiate(client_version: u32) -> Option<u32> {
+  Some(client_version.min(PROTOCOL_VERSION)).filter(|version| *version >= MIN_PROTOCOL_VERSION)
+}
+
+// Decoding and encoding of the binary messages, mirroring web/level.js
+// Version 1 clients get every field as an i32 word, see the sizes below. The compact
+// encoding, version 2 on, is bytes in the same order without the record lengths:
+// flags and message kinds are a u8, lengths, ids and colors are LEB128 varints, and
+// positions, points and materials are zigzagged varints, so small numbers are one byte.
+// Names are utf8 after their byte length. Level states come out around 3.5x smaller.
+// Size: bytes -- u32s
+#[repr(u8)]
+pub enum StateFlags {
+  Delete   = 0b00000001, // Size: 0
+  Position = 0b00000010, // Size: 8 -- 2
+  Shape    = 0b00000100, // Size: 4 + 8*length -- 1 + 2 * length
+  Material = 0b00001000, // Size: 4 -- 1
+  Hide     = 0b00010000, // Size: 0
+  Show     = 0b00100000, // Size: 0
+  Name     = 0b01000000, // Size: 4 + 4*length -- 1 + length, one char per i32
+  Color    = 0b10000000, // Size: 4 -- 1
+}
+// First i32 of every message from the server, or first byte when compact
+#[repr(i32)]
+pub enum MessageKind {
+  State = 0,        // [count, (length, id, update)*], negative count clears first
+  // [players, collected, in_exit, count, (channel, held, required)*, collect_goal, time_left]
+  // The last two came later, at the end so older clients can stop reading before them
+  Channels = 1,
+}
+
+// An ObjectUpdate as clients see it, materials are only their color codes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Update {
+  pub delete: bool,
+  pub position: Option<IVec2>,
+  pub shape: Option<Vec<IVec2>>,
+  pub material: Option<i32>,
+  pub hidden: bool,
+  pub name: Option<String>,
+  pub color: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChannelStatus {
+  pub channel: u8,
+  pub held: u8,
+  pub required: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Broadcast {
+  // Clients drop every object they know of before applying a clearing update
+  State { clear: bool, updates: Vec<(usize, Update)> },
+  Channels {
+    players: u32,
+    collected: u32,
+    in_exit: u32,
+    channels: Vec<ChannelStatus>,
+    collect_goal: u32, // 0 without one
+    time_left: Option<u32>, // Seconds
+  },
+}
+
+// Reads words off the front of a message, failing instead of panicking on short ones
+struct Reader<'a> {
+  data: &'a [i32],
+}
+impl Reader<'_> {
+  fn next(&mut self) -> Result<i32, String> {
+    let (first, rest) = self.data.split_first().ok_or("message ended early")?;
+    self.data = rest;
+    Ok(*first)
+  }
+  fn length(&mut self) -> Result<usize, String> {
+    let length = self.next()?;
+    usize::try_from(length).map_err(|_| format!("negative length {length}"))
+  }
+  fn point(&mut self) -> Result<IVec2, String> { Ok(IVec2::new(self.next()?, self.next()?)) }
+  fn take(&mut self, length: usize) -> Result<&[i32], String> {
+    if length > self.data.len() { return Err("message ended early".to_owned()) }
+    let (first, rest) = self.data.split_at(length);
+    self.data = rest;
+    Ok(first)
+  }
+}
+
+impl Update {
+  pub fn decode(data: &[i32]) -> Result<Self, String> {
+    let mut reader = Reader { data };
+    let flag = reader.next()?;
+    let has = |state_flag: StateFlags| flag & state_flag as i32 != 0;
+    let mut update = Self { delete: has(StateFlags::Delete), hidden: has(StateFlags::Hide), ..Default::default() };
+    if has(StateFlags::Position) { update.position = Some(reader.point()?); }
+    if has(StateFlags::Shape) {
+      let length = reader.length()?;
+      update.shape = Some((0..length).map(|_| reader.point()).collect::<Result<_, _>>()?);
+    }
+    if has(StateFlags::Material) { update.material = Some(reader.next()?); }
+    if has(StateFlags::Name) {
+      let length = reader.length()?;
+      let name = reader.take(length)?.iter()
+        .map(|char| u32::try_from(*char).ok().and_then(char::from_u32).ok_or(format!("bad character {char}")))
+        .collect::<Result<_, _>>()?;
+      update.name = Some(name);
+    }
+    if has(StateFlags::Color) { update.color = Some(reader.next()? as u32); }
+    if !reader.data.is_empty() { return Err(format!("{} words left over", reader.data.len())) }
+    Ok(update)
+  }
+
+  fn flags(&self) -> u8 {
+    // Parenthesized so the first if isn't parsed as a statement
+    (if self.position.is_some() { StateFlags::Position as u8 } else { 0 })    |
+    if self.shape.is_some() { StateFlags::Shape as u8 } else { 0 }            |
+    if self.material.is_some() { StateFlags::Material as u8 } else { 0 }      |
+    if self.hidden { StateFlags::Hide as u8 } else { StateFlags::Show as u8 } |
+    if self.delete { StateFlags::Delete as u8 } else { 0 }                    |
+    if self.name.is_some() { StateFlags::Name as u8 } else { 0 }              |
+    if self.color.is_some() { StateFlags::Color as u8 } else { 0 }
+  }
+
+  // Same layout as ObjectUpdate::to_binary
+  pub fn encode(&self) -> Vec<i32> {
+    let mut data = vec![self.flags() as i32];
+    if let Some(position) = self.position { data.extend([position.x, position.y]); }
+    if let Some(shape) = &self.shape {
+      data.push(shape.len() as i32);
+      data.extend(shape.iter().flat_map(|point| [point.x, point.y]));
+    }
+    if let Some(material) = self.material { data.push(material); }
+    if let Some(name) = &self.name {
+      data.push(name.chars().count() as i32);
+      data.extend(name.chars().map(|char| char as i32));
+    }
+    if let Some(color) = self.color { data.push(color as i32); }
+    data
+  }
+
+  fn decode_compact(reader: &mut ByteReader) -> Result<Self, String> {
+    let flag = reader.byte()?;
+    let has = |state_flag: StateFlags| flag & state_flag as u8 != 0;
+    let mut update = Self { delete: has(StateFlags::Delete), hidden: has(StateFlags::Hide), ..Default::default() };
+    if has(StateFlags::Position) { update.position = Some(reader.point()?); }
+    if has(StateFlags::Shape) {
+      let length = reader.varint()?;
+      update.shape = Some((0..length).map(|_| reader.point()).collect::<Result<_, _>>()?);
+    }
+    if has(StateFlags::Material) { update.material = Some(reader.signed()?); }
+    if has(StateFlags::Name) {
+      let length = reader.varint()? as usize;
+      let name = std::str::from_utf8(reader.take(length)?).map_err(|error| format!("bad name: {error}"))?;
+      update.name = Some(name.to_owned());
+    }
+    if has(StateFlags::Color) { update.color = Some(reader.varint()?); }
+    Ok(update)
+  }
+
+  fn encode_compact(&self, writer: &mut ByteWriter) {
+    writer.byte(self.flags());
+    if let Some(position) = self.position { writer.point(position); }
+    if let Some(shape) = &self.shape {
+      writer.varint(shape.len() as u32);
+      for point in shape { writer.point(*point); }
+    }
+    if let Some(material) = self.material { writer.signed(material); }
+    if let Some(name) = &self.name {
+      writer.varint(name.len() as u32);
+      writer.0.extend(name.as_bytes());
+    }
+    if let Some(color) = self.color { writer.varint(color); }
+  }
+}
+
+// Varints are 7 bits a byte, low bits first, the top bit set on every byte but the last
+struct ByteWriter(Vec<u8>);
+impl ByteWriter {
+  fn byte(&mut self, byte: u8) { self.0.push(byte); }
+  fn varint(&mut self, mut value: u32) {
+    while value >= 0x80 {
+      self.0.push(value as u8 | 0x80);
+      value >>= 7;
+    }
+    self.0.push(value as u8);
+  }
+  // Zigzag keeps small negatives small: 0, -1, 1, -2 become 0, 1, 2, 3
+  fn signed(&mut self, value: i32) { self.varint(((value << 1) ^ (value >> 31)) as u32); }
+  fn point(&mut self, point: IVec2) {
+    self.signed(point.x);
+    self.signed(point.y);
+  }
+}
+
+struct ByteReader<'a> {
+  data: &'a [u8],
+}
+impl ByteReader<'_> {
+  fn byte(&mut self) -> Result<u8, String> {
+    let (first, rest) = self.data.split_first().ok_or("message ended early")?;
+    self.data = rest;
+    Ok(*first)
+  }
+  fn varint(&mut self) -> Result<u32, String> {
+    let mut value = 0u64;
+    for shift in (0..35).step_by(7) {
+      let byte = self.byte()?;
+      value |= ((byte & 0x7f) as u64) << shift;
+      if byte & 0x80 == 0 { return u32::try_from(value).map_err(|_| format!("varint {value} is too big")) }
+    }
+    Err("varint too long".to_owned())
+  }
+  fn signed(&mut self) -> Result<i32, String> {
+    let value = self.varint()?;
+    Ok((value >> 1) as i32 ^ -((value & 1) as i32))
+  }
+  fn point(&mut self) -> Result<IVec2, String> { Ok(IVec2::new(self.signed()?, self.signed()?)) }
+  fn take(&mut self, length: usize) -> Result<&[u8], String> {
+    if length > self.data.len() { return Err("message ended early".to_owned()) }
+    let (first, rest) = self.data.split_at(length);
+    self.data = rest;
+    Ok(first)
+  }
+}
+
+impl Broadcast {
+  pub fn decode(data: &[i32]) -> Result<Self, String> {
+    let mut reader = Reader { data };
+    let kind = reader.next()?;
+    let broadcast = if kind == MessageKind::State as i32 {
+      let count = reader.next()?;
+      let mut updates = Vec::new();
+      for _ in 0..count.unsigned_abs() {
+        let length = reader.length()?;
+        let record = reader.take(length)?;
+        let (id, update) = record.split_first().ok_or("empty update record")?;
+        let id = usize::try_from(*id).map_err(|_| format!("negative object id {id}"))?;
+        updates.push((id, Update::decode(update)?));
+      }
+      Self::State { clear: count < 0, updates }
+    } else if kind == MessageKind::Channels as i32 {
+      let players = reader.next()? as u32;
+      let collected = reader.next()? as u32;
+      let in_exit = reader.next()? as u32;
+      let count = reader.length()?;
+      let channels = (0..count).map(|_| Ok(ChannelStatus {
+        channel: reader.next()? as u8,
+        held: reader.next()? as u8,
+        required: reader.next()? as u8,
+      })).collect::<Result<_, String>>()?;
+      let collect_goal = reader.next()? as u32;
+      let time_left = u32::try_from(reader.next()?).ok();
+      Self::Channels { players, collected, in_exit, channels, collect_goal, time_left }
+    } else {
+      return Err(format!("unknown message kind {kind}"))
+    };
+    if !reader.data.is_empty() { return Err(format!("{} words left over", reader.data.len())) }
+    Ok(broadcast)
+  }
+
+  // Websocket payloads aren't guaranteed to be aligned for i32s
+  pub fn decode_bytes(bytes: &[u8]) -> Result<Self, String> {
+    if !bytes.len().is_multiple_of(4) { return Err(format!("{} bytes isn't a whole number of words", bytes.len())) }
+    let data: Vec<i32> = bytes.chunks_exact(4).map(|word| i32::from_ne_bytes(word.try_into().unwrap())).collect();
+    Self::decode(&data)
+  }
+
+  pub fn encode(&self) -> Vec<i32> {
+    match self {
+      Self::State { clear, updates } => {
+        let count = updates.len() as i32;
+        let mut data = vec![MessageKind::State as i32, if *clear { -count } else { count }];
+        for (id, update) in updates {
+          let update = update.encode();
+          data.push(update.len() as i32 + 1);
+          data.push(*id as i32);
+          data.extend(update);
+        }
+        data
+      }
+      Self::Channels { players, collected, in_exit, channels, collect_goal, time_left } => {
+        let mut data = vec![
+          MessageKind::Channels as i32,
+          *players as i32,
+          *collected as i32,
+          *in_exit as i32,
+          channels.len() as i32,
+        ];
+        data.extend(channels.iter().flat_map(|status| [status.channel as i32, status.held as i32, status.required as i32]));
+        data.extend([*collect_goal as i32, time_left.map_or(-1, |seconds| seconds as i32)]);
+        data
+      }
+    }
+  }
+
+  pub fn decode_compact(bytes: &[u8]) -> Result<Self, String> {
+    let mut reader = ByteReader { data: bytes };
+    let kind = reader.byte()?;
+    let broadcast = if kind == MessageKind::State as u8 {
+      let count = reader.signed()?;
+      let updates = (0..count.unsigned_abs())
+        .map(|_| Ok((reader.varint()? as usize, Update::decode_compact(&mut reader)?)))
+        .collect::<Result<_, String>>()?;
+      Self::State { clear: count < 0, updates }
+    } else if kind == MessageKind::Channels as u8 {
+      let players = reader.varint()?;
+      let collected = reader.varint()?;
+      let in_exit = reader.varint()?;
+      let count = reader.byte()?;
+      let channels = (0..count).map(|_| Ok(ChannelStatus {
+        channel: reader.byte()?,
+        held: reader.byte()?,
+        required: reader.byte()?,
+      })).collect::<Result<_, String>>()?;
+      let collect_goal = reader.varint()?;
+      // Shifted up one, so no time limit fits in a single zero byte
+      let time_left = reader.varint()?.checked_sub(1);
+      Self::Channels { players, collected, in_exit, channels, collect_goal, time_left }
+    } else {
+      return Err(format!("unknown message kind {kind}"))
+    };
+    if !reader.data.is_empty() { return Err(format!("{} bytes left over", reader.data.len())) }
+    Ok(broadcast)
+  }
+
+  pub fn encode_compact(&self) -> Vec<u8> {
+    let mut writer = ByteWriter(Vec::new());
+    match self {
+      Self::State { clear, updates } => {
+        writer.byte(MessageKind::State as u8);
+        let count = updates.len() as i32;
+        writer.signed(if *clear { -count } else { count });
+        for (id, update) in updates {
+          writer.varint(*id as u32);
+          update.encode_compact(&mut writer);
+        }
+      }
+      Self::Channels { players, collected, in_exit, channels, collect_goal, time_left } => {
+        writer.byte(MessageKind::Channels as u8);
+        writer.varint(*players);
+        writer.varint(*collected);
+        writer.varint(*in_exit);
+        writer.byte(channels.len() as u8);
+        for status in channels { writer.0.extend([status.channel, status.held, status.required]); }
+        writer.varint(*collect_goal);
+        writer.varint(time_left.map_or(0, |seconds| seconds.saturating_add(1)));
+      }
+    }
+    writer.0
+  }
+
+  // What a client speaking the given version is sent for a broadcast built as words
+  pub fn payload(words: &[i32], version: u32) -> Result<Vec<u8>, String> {
+    if version < COMPACT_VERSION { return Ok(bytemuck::cast_slice(words).to_vec()) }
+    Ok(Self::decode(words)?.encode_compact())
+  }
+}
+
+// Mouse movement is the only binary message clients send, [dx, dy]
+pub fn encode_movement(delta: IVec2) -> Vec<u8> {
+  bytemuck::cast_slice(&[delta.x, delta.y]).to_vec()
+}
+
+pub fn decode_movement(bytes: &[u8]) -> Result<IVec2, String> {
+  let data: [i32; 2] = bytemuck::try_pod_read_unaligned(bytes)
+    .map_err(|_| format!("movement should be 8 bytes, got {}", bytes.len()))?;
+  Ok(IVec2::from_array(data))
+}
@@ -0,0 +1,79 @@
+use glam::IVec2;
+use mouse_game::Simulation;
+use mouse_game::game::{ClientMessage, Command, Material, ServerMessage};
+
+#[test]
+fn frozen_games_ignore_movement() {
+  let mut sim = Simulation::new("level1");
+  let player = sim.add_player();
+  sim.command(Command::Freeze(true));
+  for _ in 0..20 {
+    sim.push(player, IVec2::new(400, 0));
+    sim.step(1);
+  }
+  sim.command(Command::Freeze(false));
+  sim.step(20);
+  assert_eq!(sim.position(player), Some(IVec2::ZERO));
+}
+
+#[test]
+fn kicks_need_an_unambiguous_player() {
+  let mut sim = Simulation::new("level1");
+  let players = [sim.add_player(), sim.add_player()];
+  // Both skipped the nickname
+  sim.command(Command::Kick("Mouse".to_owned()));
+  assert!(players.iter().all(|player| sim.position(*player).is_some()));
+
+  let mouse = sim.object_of(players[1]).unwrap();
+  sim.command(Command::Kick(mouse.to_string()));
+  assert!(sim.position(players[0]).is_some());
+  assert_eq!(sim.position(players[1]), None);
+  assert!(sim.messages(players[1]).iter().any(|message| matches!(message, ServerMessage::Rejected { .. })));
+}
+
+#[test]
+fn kicked_players_let_go_of_buttons() {
+  const BUTTON: usize = 4;
+  let mut sim = Simulation::new("level1");
+  let player = sim.add_player();
+  assert!(sim.walk_to(player, IVec2::new(-5, -280), 1000));
+  assert_eq!(sim.material(BUTTON), Some(Material::Button(0, 1)));
+  sim.command(Command::Kick(sim.object_of(player).unwrap().to_string()));
+  sim.step(2);
+  assert_eq!(sim.channel_held(0), 0);
+  assert_eq!(sim.material(BUTTON), Some(Material::Button(0, 0)));
+}
+
+#[test]
+fn tick_rate_only_changes_smoothness() {
+  let mut normal = Simulation::new("level4");
+  normal.step(150);
+  let mut doubled = Simulation::new("level4");
+  doubled.command(Command::TickRate(100));
+  doubled.step(300);
+  for (normal, doubled) in normal.animated_positions().into_iter().zip(doubled.animated_positions()) {
+    assert!((normal - doubled).abs().max_element() <= 1, "{normal} vs {doubled}");
+  }
+}
+
+#[test]
+fn failed_logins_are_limited_per_connection() {
+  let mut sim = Simulation::new("level1");
+  sim.set_admin_secret("hunter2");
+  let guesser = sim.add_player();
+  let other = sim.add_player();
+  let login = |sim: &mut Simulation, id, secret: &str| {
+    sim.send(id, &ClientMessage::Admin { secret: secret.to_owned() });
+    sim.messages(id).into_iter()
+      .find_map(|message| if let ServerMessage::Admin { authenticated } = message { Some(authenticated) } else { None })
+      .unwrap()
+  };
+  for guess in ["hunter1", "hunter3", "hunter4"] { assert!(!login(&mut sim, guesser, guess)); }
+  // Out of attempts, so even the right secret is turned away for now
+  assert!(!login(&mut sim, guesser, "hunter2"));
+  assert!(login(&mut sim, other, "hunter2"));
+  // Counted by uptime, which keeps going while the game is frozen
+  sim.command(Command::Freeze(true));
+  sim.step(30 * 50);
+  assert!(login(&mut sim, guesser, "hunter2"));
+}
@@ -0,0 +1,77 @@
+use glam::IVec2;
+use mouse_game::Simulation;
+use mouse_game::game::{Command, Material};
+
+const BUTTON: usize = 4;
+
+#[test]
+fn bots_stay_out_unless_enabled() {
+  let mut sim = Simulation::new("level1");
+  sim.add_player();
+  sim.step(100);
+  assert_eq!(sim.mice(), 1);
+}
+
+#[test]
+fn bots_fill_level1_button() {
+  let mut sim = Simulation::new("level1");
+  let player = sim.add_player();
+  sim.command(Command::Bots(true));
+  sim.step(1);
+  assert_eq!(sim.mice(), 3);
+  sim.step(500);
+  // The bots hold what the missing players would, the rest is still up to us
+  assert_eq!(sim.channel_held(0), 2);
+  assert_eq!(sim.wins(), 0);
+  assert!(!sim.walk_to(player, IVec2::new(50, -285), 1000));
+  assert_eq!(sim.wins(), 1);
+  assert_eq!(sim.level(), "level2");
+}
+
+#[test]
+fn bots_leave_when_humans_join() {
+  let mut sim = Simulation::new("level1");
+  sim.add_player();
+  sim.command(Command::Bots(true));
+  sim.step(1);
+  assert_eq!(sim.mice(), 3);
+  sim.add_player();
+  sim.step(1);
+  assert_eq!(sim.mice(), 3);
+  sim.add_player();
+  sim.add_player();
+  sim.step(1);
+  assert_eq!(sim.mice(), 4);
+}
+
+#[test]
+fn disabling_bots_removes_them() {
+  let mut sim = Simulation::new("level1");
+  sim.add_player();
+  sim.command(Command::Bots(true));
+  sim.step(1);
+  assert_eq!(sim.mice(), 3);
+  sim.step(500);
+  assert_eq!(sim.material(BUTTON), Some(Material::Button(0, 2)));
+  sim.command(Command::Bots(false));
+  sim.step(1);
+  assert_eq!(sim.mice(), 1);
+  // Their mice are gone, so nothing is left standing on the button
+  assert_eq!(sim.channel_held(0), 0);
+  assert_eq!(sim.material(BUTTON), Some(Material::Button(0, 0)));
+}
+
+#[test]
+fn bots_dont_have_to_reach_the_exit() {
+  let mut sim = Simulation::new("level6");
+  let player = sim.add_player();
+  sim.command(Command::Bots(true));
+  sim.step(300);
+  // A bot holds the gate's button while we go through the door on our own
+  assert!(sim.channel_active(1));
+  for waypoint in [IVec2::new(150, -9), IVec2::new(300, -9), IVec2::new(565, -9)] {
+    assert!(sim.walk_to(player, waypoint, 1000), "stuck at {:?}", sim.position(player));
+  }
+  sim.step(5);
+  assert_eq!(sim.wins(), 1);
+}
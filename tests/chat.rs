@@ -0,0 +1,79 @@
+use mouse_game::Simulation;
+use mouse_game::game::{Command, ServerMessage};
+use mouse_game::protocol::ClientMessage;
+use uuid::Uuid;
+
+fn say(sim: &mut Simulation, id: Uuid, text: &str) {
+  sim.send(id, &ClientMessage::Chat { text: text.to_owned() });
+}
+
+// Texts of the chat messages the player was sent since last asked
+fn heard(sim: &mut Simulation, id: Uuid) -> Vec<String> {
+  sim.messages(id).into_iter()
+    .filter_map(|message| if let ServerMessage::Chat { text, .. } = message { Some(text) } else { None })
+    .collect()
+}
+
+#[test]
+fn messages_are_cleaned_up_and_capped() {
+  let mut sim = Simulation::new("level1");
+  let player = sim.add_player();
+  say(&mut sim, player, "  \u{7}  ");
+  say(&mut sim, player, &"squeak".repeat(50));
+  say(&mut sim, player, " hi\nthere ");
+  let texts = heard(&mut sim, player);
+  assert_eq!(texts.len(), 2);
+  assert_eq!(texts[0].chars().count(), 200);
+  assert_eq!(texts[1], "hithere");
+}
+
+#[test]
+fn senders_are_rate_limited() {
+  let mut sim = Simulation::new("level1");
+  let player = sim.add_player();
+  for index in 0..6 { say(&mut sim, player, &index.to_string()); }
+  assert_eq!(heard(&mut sim, player), ["0", "1", "2", "3", "4"]);
+  // 5 seconds later the window has moved on
+  sim.step(250);
+  say(&mut sim, player, "again");
+  assert_eq!(heard(&mut sim, player), ["again"]);
+}
+
+#[test]
+fn freezing_doesnt_mute_anyone() {
+  let mut sim = Simulation::new("level1");
+  let player = sim.add_player();
+  sim.command(Command::Freeze(true));
+  for index in 0..5 { say(&mut sim, player, &index.to_string()); }
+  sim.step(250);
+  say(&mut sim, player, "still frozen");
+  assert_eq!(heard(&mut sim, player).last().map(String::as_str), Some("still frozen"));
+}
+
+#[test]
+fn newcomers_get_the_history() {
+  let mut sim = Simulation::new("level1");
+  let player = sim.add_player();
+  sim.send(player, &ClientMessage::Join { name: "Pip".to_owned(), color: Some(0x123456) });
+  say(&mut sim, player, "first");
+  say(&mut sim, player, "second");
+  let newcomer = sim.add_player();
+  let history: Vec<_> = sim.messages(newcomer).into_iter()
+    .filter_map(|message| if let ServerMessage::Chat { name, color, text } = message { Some((name, color, text)) } else { None })
+    .collect();
+  assert_eq!(history, [
+    ("Pip".to_owned(), Some(0x123456), "first".to_owned()),
+    ("Pip".to_owned(), Some(0x123456), "second".to_owned()),
+  ]);
+}
+
+#[test]
+fn spectators_chat_without_a_mouse() {
+  let mut sim = Simulation::new("level1");
+  let player = sim.add_player();
+  let spectator = sim.add_player();
+  sim.send(spectator, &ClientMessage::Spectate);
+  say(&mut sim, spectator, "just watching");
+  let message = sim.messages(player).into_iter().find(|message| matches!(message, ServerMessage::Chat { .. }));
+  assert!(matches!(message, Some(ServerMessage::Chat { name, color: None, .. }) if name == "Spectator"));
+}
@@ -0,0 +1,183 @@
+use glam::IVec2;
+use mouse_game::{Level, Simulation};
+
+#[test]
+fn every_level_loads_and_runs() {
+  for level in Level::available() {
+    let mut sim = Simulation::new(&level);
+    let players = [sim.add_player(), sim.add_player()];
+    sim.step(300);
+    assert_eq!(sim.level(), level);
+    for player in players { assert!(sim.position(player).is_some(), "{level} lost a player"); }
+  }
+}
+
+#[test]
+fn level1_button_needs_three_players() {
+  let mut sim = Simulation::new("level1");
+  let first = sim.add_player();
+  let second = sim.add_player();
+  assert!(sim.walk_to(first, IVec2::new(-60, -280), 1000));
+  assert!(sim.walk_to(second, IVec2::new(40, -280), 1000));
+  sim.step(5);
+  assert_eq!(sim.channel_held(0), 2);
+  assert!(!sim.channel_active(0));
+  assert_eq!(sim.level(), "level1");
+
+  let third = sim.add_player();
+  sim.walk_to(third, IVec2::new(-10, -280), 1000);
+  sim.step(5);
+  assert_eq!(sim.wins(), 1);
+  assert_eq!(sim.level(), "level2");
+}
+
+#[test]
+fn level3_big_death_restarts_the_level() {
+  let mut sim = Simulation::new("level3");
+  let player = sim.add_player();
+  assert!(sim.walk_to(player, IVec2::new(100, -30), 1000));
+  assert!(!sim.walk_to(player, IVec2::new(100, -95), 1000));
+  assert_eq!(sim.deaths(), 1);
+  assert_eq!(sim.level(), "level3");
+  assert_eq!(sim.position(player), Some(IVec2::ZERO));
+}
+
+#[test]
+fn level4_sweeper_respawns_idle_players() {
+  let mut sim = Simulation::new("level4");
+  let player = sim.add_player();
+  sim.step(800);
+  assert!(sim.deaths() > 0);
+  assert_eq!(sim.level(), "level4");
+  assert!(sim.position(player).is_some());
+}
+
+#[test]
+fn level5_needs_every_item_before_the_exit() {
+  let mut sim = Simulation::new("level5");
+  let player = sim.add_player();
+  assert!(sim.walk_to(player, IVec2::new(490, 340), 1000));
+  sim.step(5);
+  assert_eq!(sim.wins(), 0);
+
+  for item in [IVec2::new(385, -210), IVec2::new(-5, 290), IVec2::new(-405, -210)] {
+    assert!(sim.walk_to(player, item, 1000));
+    sim.step(5);
+  }
+  assert_eq!(sim.collected(), 3);
+  assert_eq!(sim.collected_by(player), 3);
+  assert_eq!(sim.wins(), 0);
+  // Winning moves straight on, before the mouse gets all the way in
+  assert!(!sim.walk_to(player, IVec2::new(490, 340), 1000));
+  assert_eq!(sim.wins(), 1);
+  assert_eq!(sim.level(), "level6");
+}
+
+#[test]
+fn level5_restarts_when_time_runs_out() {
+  let mut sim = Simulation::new("level5");
+  sim.add_player();
+  let loads = sim.level_loads();
+  // 45 seconds at 50 ticks per second
+  sim.step(2240);
+  assert_eq!(sim.level_loads(), loads);
+  sim.step(20);
+  assert_eq!(sim.level_loads(), loads + 1);
+  assert_eq!(sim.level(), "level5");
+}
+
+#[test]
+fn level6_gate_needs_someone_on_its_button() {
+  const GATE: usize = 9;
+  let mut sim = Simulation::new("level6");
+  // Shut, the gate keeps everyone out of the exit
+  let runner = sim.add_player();
+  for waypoint in [IVec2::new(150, -9), IVec2::new(300, -9)] {
+    assert!(sim.walk_to(runner, waypoint, 1000), "stuck at {:?}", sim.position(runner));
+  }
+  assert!(!sim.walk_to(runner, IVec2::new(565, -9), 300));
+  let holder = sim.add_player();
+  assert!(sim.walk_to(holder, IVec2::new(-405, -9), 1000), "stuck at {:?}", sim.position(holder));
+  assert!(sim.channel_active(1));
+  sim.step(100);
+  assert!(sim.object_position(GATE).unwrap().y < -150, "gate at {}", sim.object_position(GATE).unwrap());
+  assert!(sim.walk_to(runner, IVec2::new(565, -9), 1000), "stuck at {:?}", sim.position(runner));
+  sim.step(5);
+  // Everyone has to make it, and the gate closes again behind the runner
+  assert_eq!(sim.wins(), 0);
+  assert!(sim.walk_to(holder, IVec2::new(150, -9), 1000), "stuck at {:?}", sim.position(holder));
+  sim.step(100);
+  assert!(sim.object_position(GATE).unwrap().y > -75, "gate at {}", sim.object_position(GATE).unwrap());
+}
+
+// Levels that only exercise an edge case live in tests/levels, out of the rotation
+const FIXTURES: &str = "../tests/levels";
+
+#[test]
+fn collecting_next_to_a_dynamic_wall() {
+  let mut sim = Simulation::new(&format!("{FIXTURES}/item_on_crate"));
+  let player = sim.add_player();
+  // Deleting the item ends its overlap with the wall too, which isn't anyone's event
+  assert!(sim.walk_to(player, IVec2::new(92, -5), 1000), "stuck at {:?}", sim.position(player));
+  sim.step(5);
+  assert_eq!(sim.collected(), 1);
+  assert_eq!(sim.wins(), 1);
+}
+
+#[test]
+fn scaled_requirements_follow_the_roster() {
+  let mut sim = Simulation::new(&format!("{FIXTURES}/scaled_buttons"));
+  let first = sim.add_player();
+  // Half the players rounded up, and two but never more than there are
+  assert_eq!((sim.required(0), sim.required(1)), (1, 1));
+  let mut players = vec![first];
+  for _ in 0..4 { players.push(sim.add_player()); }
+  assert_eq!((sim.required(0), sim.required(1)), (3, 2));
+
+  // Players who dropped out don't count while their mouse waits for them
+  sim.remove_player(players.pop().unwrap());
+  sim.remove_player(players.pop().unwrap());
+  assert_eq!((sim.required(0), sim.required(1)), (2, 2));
+  sim.remove_player(players.pop().unwrap());
+  sim.remove_player(players.pop().unwrap());
+  assert_eq!((sim.required(0), sim.required(1)), (1, 1));
+  sim.step(1550);
+  assert_eq!(sim.mice(), 1);
+  assert_eq!((sim.required(0), sim.required(1)), (1, 1));
+}
+
+#[test]
+fn broken_joints_are_skipped() {
+  // Joins a missing object, an object to itself and along no axis, before one that's fine
+  let mut sim = Simulation::new(&format!("{FIXTURES}/bad_joints"));
+  sim.add_player();
+  sim.step(50);
+  assert_eq!(sim.object_position(1), Some(IVec2::new(-200, -100)));
+}
+
+#[test]
+fn pink_walls_only_let_the_pink_team_through() {
+  // Two teams, so the second player joins the pink one
+  let mut sim = Simulation::new(&format!("{FIXTURES}/pink_wall"));
+  let plain = sim.add_player();
+  assert!(!sim.walk_to(plain, IVec2::new(200, -100), 500));
+  assert!(sim.position(plain).unwrap().x < 100);
+  let pink = sim.add_player();
+  assert!(sim.walk_to(pink, IVec2::new(200, 100), 1000));
+
+  // Teams past the last one in "blocks" are dropped instead of overflowing
+  assert!(!sim.walk_to(plain, IVec2::new(-400, -100), 500));
+  assert!(sim.walk_to(pink, IVec2::new(-400, 100), 1000));
+}
+
+#[test]
+fn platforms_carry_their_riders() {
+  let mut sim = Simulation::new(&format!("{FIXTURES}/carry_platform"));
+  let rider = sim.add_player();
+  let start = sim.position(rider).unwrap();
+  sim.step(300);
+  // Damping would leave a rider far behind if the push didn't make up for it
+  let moved = sim.position(rider).unwrap() - start;
+  assert!((moved.x - 300).abs() <= 5, "rider moved {moved}");
+  assert!(moved.y.abs() <= 5, "rider moved {moved}");
+}
@@ -0,0 +1,234 @@
+use glam::IVec2;
+use mouse_game::game::{Command, Identity, Material};
+use mouse_game::protocol::{decode_movement, encode_movement, negotiate, ChannelStatus, ClientMessage, ServerMessage, COMPACT_VERSION, PROTOCOL_VERSION};
+use mouse_game::{Broadcast, Level, ObjectUpdate, Simulation, Update};
+
+fn round_trip(update: &ObjectUpdate) -> Update {
+  let binary = update.to_binary();
+  let decoded = Update::decode(&binary).unwrap();
+  assert_eq!(decoded.encode(), binary);
+  decoded
+}
+
+#[test]
+fn object_updates_round_trip() {
+  assert_eq!(round_trip(&ObjectUpdate::new()), Update::default());
+
+  let mut update = ObjectUpdate::new();
+  update.position(IVec2::new(-650, 490))
+    .shape(vec![IVec2::ZERO, IVec2::new(0, 16), IVec2::new(11, 12)])
+    .material(Material::Collectible)
+    .hidden(true)
+    .identity(&Identity { name: "Mäuschen 🐭".to_owned(), color: 0xff8800 });
+  assert_eq!(round_trip(&update), Update {
+    delete: false,
+    position: Some(IVec2::new(-650, 490)),
+    shape: Some(vec![IVec2::ZERO, IVec2::new(0, 16), IVec2::new(11, 12)]),
+    material: Some(7),
+    hidden: true,
+    name: Some("Mäuschen 🐭".to_owned()),
+    color: Some(0xff8800),
+  });
+
+  let mut update = ObjectUpdate::new();
+  update.delete();
+  assert!(round_trip(&update).delete);
+}
+
+#[test]
+fn state_broadcasts_round_trip() {
+  let mut sim = Simulation::new("level1");
+  let player = sim.add_player();
+  let object_id = sim.object_of(player).unwrap();
+
+  // The first broadcast of a level replaces everything clients had
+  let message = sim.state_message().unwrap();
+  let decoded = Broadcast::decode(&message).unwrap();
+  assert_eq!(decoded.encode(), message);
+  let Broadcast::State { clear, updates } = decoded else { panic!("expected a state broadcast") };
+  assert!(clear);
+  assert_eq!(updates.len(), sim.object_count());
+  let (_, mouse) = updates.iter().find(|(id, _)| *id == object_id).unwrap();
+  assert_eq!(mouse.material, Some(0));
+  assert_eq!(mouse.position, Some(IVec2::ZERO));
+
+  sim.push(player, IVec2::new(200, 0));
+  sim.step(1);
+  let message = sim.state_message().unwrap();
+  let decoded = Broadcast::decode(&message).unwrap();
+  assert_eq!(decoded.encode(), message);
+  let Broadcast::State { clear, updates } = decoded else { panic!("expected a state broadcast") };
+  assert!(!clear);
+  let (_, mouse) = updates.iter().find(|(id, _)| *id == object_id).unwrap();
+  assert!(mouse.position.unwrap().x > 0);
+  assert_eq!(mouse.shape, None);
+}
+
+#[test]
+fn channel_broadcasts_round_trip() {
+  let mut sim = Simulation::new("level1");
+  sim.add_player();
+  sim.add_player();
+  let message = sim.channels_message().unwrap();
+  let decoded = Broadcast::decode(&message).unwrap();
+  assert_eq!(decoded.encode(), message);
+  assert_eq!(decoded, Broadcast::Channels {
+    players: 2,
+    collected: 0,
+    in_exit: 0,
+    channels: vec![ChannelStatus { channel: 0, held: 0, required: 3 }],
+    collect_goal: 0,
+    time_left: None,
+  });
+  assert_eq!(sim.channels_message(), None);
+}
+
+#[test]
+fn channel_broadcasts_carry_the_goal_and_countdown() {
+  let mut sim = Simulation::new("level5");
+  sim.add_player();
+  let status = |message: Option<Vec<i32>>| match Broadcast::decode(&message.unwrap()).unwrap() {
+    Broadcast::Channels { collect_goal, time_left, .. } => (collect_goal, time_left),
+    _ => panic!("expected a channels broadcast"),
+  };
+  assert_eq!(status(sim.channels_message()), (3, Some(45)));
+  // Only sent again once a whole second has gone by
+  sim.step(25);
+  assert_eq!(sim.channels_message(), None);
+  sim.step(25);
+  assert_eq!(status(sim.channels_message()), (3, Some(44)));
+}
+
+#[test]
+fn byte_payloads_decode() {
+  let message = Broadcast::State { clear: false, updates: vec![(3, Update { position: Some(IVec2::new(1, -1)), ..Default::default() })] };
+  let bytes: Vec<u8> = message.encode().iter().flat_map(|word| word.to_ne_bytes()).collect();
+  assert_eq!(Broadcast::decode_bytes(&bytes).unwrap(), message);
+  // Unaligned, as websocket payloads can be
+  let mut shifted = vec![0];
+  shifted.extend(&bytes);
+  assert_eq!(Broadcast::decode_bytes(&shifted[1..]).unwrap(), message);
+  assert!(Broadcast::decode_bytes(&bytes[1..]).is_err());
+
+  assert_eq!(decode_movement(&encode_movement(IVec2::new(-7, 12))), Ok(IVec2::new(-7, 12)));
+  assert!(decode_movement(&[0; 12]).is_err());
+}
+
+#[test]
+fn malformed_broadcasts_are_rejected() {
+  assert!(Broadcast::decode(&[]).is_err());
+  assert!(Broadcast::decode(&[7]).is_err());
+  // Record claims more words than the message has
+  assert!(Broadcast::decode(&[0, 1, 5, 0, 0b10]).is_err());
+  // Position flag without the position
+  assert!(Broadcast::decode(&[0, 1, 2, 0, 0b10]).is_err());
+  assert!(Broadcast::decode(&[1, 0, 0, 0, 0, 9]).is_err());
+  assert!(Update::decode(&[0b01000000, 1, -5]).is_err());
+}
+
+#[test]
+fn versions_are_negotiated_down_or_rejected() {
+  assert_eq!(negotiate(PROTOCOL_VERSION), Some(PROTOCOL_VERSION));
+  assert_eq!(negotiate(PROTOCOL_VERSION + 5), Some(PROTOCOL_VERSION));
+  // Clients from before the handshake don't announce anything
+  assert_eq!(negotiate(0), None);
+  // Word clients are still talked to in words
+  assert_eq!(negotiate(1), Some(1));
+}
+
+#[test]
+fn players_are_told_their_mouse() {
+  let mut sim = Simulation::new("level1");
+  let player = sim.add_player();
+  let mouse = sim.object_of(player);
+  assert!(mouse.is_some());
+  assert!(matches!(sim.messages(player)[0], ServerMessage::Hello { object_id, .. } if object_id == mouse));
+
+  // Loading hands out new ids, so everyone hears theirs again
+  sim.command(Command::Load("level2".to_owned()));
+  sim.step(1);
+  let mouse = sim.object_of(player).unwrap();
+  assert!(sim.messages(player).iter().any(|message| matches!(message, ServerMessage::Welcome { object_id } if *object_id == mouse)));
+
+  sim.send(player, &ClientMessage::Spectate);
+  sim.send(player, &ClientMessage::Play);
+  let mouse = sim.object_of(player).unwrap();
+  assert!(sim.messages(player).iter().any(|message| matches!(message, ServerMessage::Welcome { object_id } if *object_id == mouse)));
+}
+
+#[test]
+fn away_players_are_labelled_for_newcomers() {
+  let mut sim = Simulation::new("level1");
+  let player = sim.add_player();
+  let mouse = sim.object_of(player).unwrap();
+  sim.remove_player(player);
+  // Late joiners get the full state, which has to say so too
+  let update = round_trip(&sim.full_update(mouse));
+  assert_eq!(update.name.as_deref(), Some("Mouse (away)"));
+
+  sim.command(Command::Load("level2".to_owned()));
+  sim.step(1);
+  // Loading gives the ghost a new mouse, sent along with the rest of the level
+  let Broadcast::State { updates, .. } = Broadcast::decode(&sim.state_message().unwrap()).unwrap() else { panic!("expected a state broadcast") };
+  assert!(updates.iter().any(|(_, update)| update.name.as_deref() == Some("Mouse (away)")));
+}
+
+#[test]
+fn compact_broadcasts_round_trip() {
+  let updates = vec![
+    (0, Update { position: Some(IVec2::new(-300, 1_000_000)), shape: Some(vec![IVec2::new(-1, 0), IVec2::new(i32::MIN, i32::MAX)]), material: Some(-2), ..Default::default() }),
+    (70_000, Update { delete: true, hidden: true, ..Default::default() }),
+    (5, Update { name: Some("Mouse \u{1f42d}".to_owned()), color: Some(u32::MAX), ..Default::default() }),
+  ];
+  let messages = [
+    Broadcast::State { clear: true, updates: updates.clone() },
+    Broadcast::State { clear: false, updates },
+    Broadcast::Channels { players: 300, collected: 2, in_exit: 0, channels: vec![ChannelStatus { channel: 15, held: 1, required: 255 }], collect_goal: 0, time_left: None },
+    Broadcast::Channels { players: 1, collected: 0, in_exit: 1, channels: Vec::new(), collect_goal: 3, time_left: Some(0) },
+  ];
+  for message in messages {
+    let bytes = message.encode_compact();
+    assert_eq!(Broadcast::decode_compact(&bytes).unwrap(), message);
+    assert!(Broadcast::decode_compact(&bytes[..bytes.len() - 1]).is_err());
+  }
+  // Unterminated and oversized varints
+  assert!(Broadcast::decode_compact(&[0, 0x80]).is_err());
+  assert!(Broadcast::decode_compact(&[0, 0xff, 0xff, 0xff, 0xff, 0x7f]).is_err());
+  assert!(Broadcast::decode_compact(&[1, 0, 0, 0, 0, 9]).is_err());
+}
+
+// Measured with two players: full states are about 3.5x smaller (level1 is 532 bytes
+// as words, 152 compact) and a tick of both mice moving 4-5x (48 bytes to 10)
+#[test]
+fn compact_encoding_shrinks_level_broadcasts() {
+  for level in Level::available() {
+    let mut sim = Simulation::new(&level);
+    let players = [sim.add_player(), sim.add_player()];
+    let full = sim.state_message().unwrap();
+    for player in players { sim.push(player, IVec2::new(100, -50)); }
+    sim.step(1);
+    let moved = sim.state_message().unwrap();
+    for words in [full, moved] {
+      let compact = Broadcast::payload(&words, COMPACT_VERSION).unwrap();
+      assert_eq!(Broadcast::decode_compact(&compact).unwrap(), Broadcast::decode(&words).unwrap());
+      assert_eq!(Broadcast::payload(&words, 1).unwrap().len(), words.len() * 4);
+      assert!(compact.len() * 3 < words.len() * 4, "{level} only went from {} to {} bytes", words.len() * 4, compact.len());
+    }
+  }
+}
+
+#[test]
+fn pings_stay_in_the_level_and_are_rate_limited() {
+  let mut sim = Simulation::new("level1");
+  let player = sim.add_player();
+  sim.send(player, &ClientMessage::Ping { position: Some(IVec2::new(i32::MIN, 0)) });
+  // Too soon after the last one
+  sim.send(player, &ClientMessage::Ping { position: Some(IVec2::new(10, 10)) });
+  let (min, max) = sim.bounds();
+  assert_eq!(sim.pings(), vec![IVec2::new(min.x, 0) - 6]);
+  assert!(sim.pings()[0].cmple(max).all());
+
+  sim.step(50);
+  sim.send(player, &ClientMessage::Ping { position: Some(IVec2::new(10, 10)) });
+  assert_eq!(sim.pings().len(), 2);
+}
@@ -0,0 +1,82 @@
+use glam::IVec2;
+use mouse_game::Simulation;
+use mouse_game::game::{replay, ServerMessage};
+use mouse_game::protocol::ClientMessage;
+
+#[test]
+fn recorded_sessions_replay_exactly() {
+  let path = std::env::temp_dir().join(format!("mouse_game_replay_{}.jsonl", std::process::id()));
+  let path = path.to_str().unwrap();
+  let mut sim = Simulation::new("level1");
+  sim.set_admin_secret("hunter2");
+  sim.record(path).unwrap();
+
+  let first = sim.add_player();
+  let second = sim.add_player();
+  sim.send(first, &ClientMessage::Join { name: "First".to_owned(), color: None });
+  for tick in 0..60 {
+    sim.push(first, IVec2::new(150, tick % 7 - 3));
+    sim.push(second, IVec2::new(-80, 120));
+    sim.step(1);
+  }
+  let token = sim.messages(first).into_iter()
+    .find_map(|message| if let ServerMessage::Session { token } = message { Some(token) } else { None })
+    .unwrap();
+  sim.remove_player(first);
+  sim.step(20);
+  // Comes back on a new connection and takes the frozen mouse over again
+  let returning = sim.add_player();
+  sim.send(returning, &ClientMessage::Resume { token });
+  assert!(sim.walk_to(returning, IVec2::new(-200, 100), 500));
+  sim.send(second, &ClientMessage::Admin { secret: "hunter2".to_owned() });
+  let loads = sim.level_loads();
+  sim.send(second, &ClientMessage::Command { command: "restart".to_owned() });
+  assert_eq!(sim.level_loads(), loads + 1);
+  sim.send(second, &ClientMessage::Ping { position: None });
+  for _ in 0..40 {
+    sim.push(returning, IVec2::new(60, -90));
+    sim.step(1);
+  }
+  drop(sim);
+
+  // Replays don't need the secret, and the recording doesn't give it away
+  let recording = std::fs::read_to_string(path).unwrap();
+  assert!(!recording.contains("hunter2"));
+  assert_eq!(replay(path), Ok(None));
+
+  // A different position anywhere in the recording is caught at its tick
+  let mut lines: Vec<String> = recording.lines().map(str::to_owned).collect();
+  let tampered = &mut lines[31];
+  let checksum = tampered.rsplit_once("\"checksum\":").unwrap().1.trim_end_matches('}').to_owned();
+  *tampered = tampered.replace(&checksum, &(checksum.parse::<u64>().unwrap() ^ 1).to_string());
+  std::fs::write(path, lines.join("\n")).unwrap();
+  assert_eq!(replay(path), Ok(Some(30)));
+  std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn ghosts_expiring_together_replay_exactly() {
+  let path = std::env::temp_dir().join(format!("mouse_game_ghosts_{}.jsonl", std::process::id()));
+  let path = path.to_str().unwrap();
+  let mut sim = Simulation::new("level1");
+  sim.record(path).unwrap();
+
+  let players: Vec<_> = (0..4).map(|_| sim.add_player()).collect();
+  for (index, player) in players.iter().enumerate() {
+    sim.push(*player, IVec2::new(index as i32 * 60 - 90, 100));
+    sim.step(1);
+  }
+  for player in &players { sim.remove_player(*player); }
+  // Past the grace period, then the freed ids go to whoever turns up next
+  sim.step(1550);
+  for index in 0..4 {
+    let player = sim.add_player();
+    sim.push(player, IVec2::new(index * 40, -index * 40));
+    sim.step(1);
+  }
+  sim.step(20);
+  drop(sim);
+
+  assert_eq!(replay(path), Ok(None));
+  std::fs::remove_file(path).unwrap();
+}
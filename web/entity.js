@@ -8,6 +8,7 @@ export default class Entity {
     this.outline = false;
     this.points = []; // Points = [Vec2]
     this.hidden = false;
+    this.name = null;
   }
 
   update_material(material) {
@@ -41,10 +42,22 @@ export default class Entity {
         this.color = "purple";
         this.priority = 1;
         break;
+      case 7: // Collectible
+        this.color = "gold";
+        this.priority = 2;
+        break;
+      case 8: // Exit
+        this.color = "lightblue";
+        this.priority = 1;
+        break;
+      case 9: // Ping
+        this.color = "orange";
+        this.priority = 4;
+        break;
     }
   }
   
-  render(ctx) {
+  render(ctx, own = false) {
     let center = new Vec2(window.innerWidth / 2, window.innerHeight / 2);
     let real_pos = this.pos.add(center);
     if (this.hidden) { return }
@@ -59,9 +72,15 @@ export default class Entity {
     ctx.fill();
     if (this.outline) {
       ctx.strokeStyle = "black";
-      ctx.lineWidth = 1;
+      // Thicker around our own mouse so it stands out from the others
+      ctx.lineWidth = own ? 3 : 1;
       ctx.stroke();
     }
+    if (this.name) {
+      ctx.fillStyle = "black";
+      ctx.font = "12px sans-serif";
+      ctx.fillText(this.name, real_pos.x + 12, real_pos.y - 2);
+    }
   }
 }
 
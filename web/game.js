@@ -1,5 +1,6 @@
 import Vec2 from './math.js';
 import Level from './level.js';
+import Reader from './reader.js';
 const canvas = document.getElementById("canvas");
 const ctx = canvas.getContext("2d");
 
@@ -10,19 +11,62 @@ function gameLoop(_timestamp) {
 }
 requestAnimationFrame(gameLoop);
 
-canvas.addEventListener("contextmenu", e => e.preventDefault());
+// Right click pings our mouse's position, or the clicked spot when unlocked
+canvas.addEventListener("contextmenu", e => {
+  e.preventDefault();
+  if (!connected) { return; }
+  let position = document.pointerLockElement === canvas ? null : [
+    Math.round(e.clientX - window.innerWidth / 2),
+    Math.round(e.clientY - window.innerHeight / 2),
+  ];
+  socket.send(JSON.stringify({ type: "Ping", position }));
+});
 
-// Connect to WebSocket
+// Connect to WebSocket, announcing which protocol we speak
+const PROTOCOL_VERSION = 2;
 let connected = false;
-const socket = new WebSocket("ws://localhost:8080/ws");
-socket.onopen = () => { connected = true; };
-socket.onclose = () => { connected = false; };
+let rejected = false;
+let server = null; // From the hello: version, tick_rate, object_id, features
+const spectate = new URLSearchParams(location.search).has("spectate");
+const socket = new WebSocket("ws://localhost:8080/ws?version=" + PROTOCOL_VERSION + (spectate ? "&spectate" : ""));
+const role = document.getElementById("role");
+let spectating = spectate;
+role.addEventListener("click", () => {
+  socket.send(JSON.stringify({ type: spectating ? "Play" : "Spectate" }));
+});
+const nickname = document.getElementById("nickname");
+const color = document.getElementById("color");
+nickname.value = localStorage.getItem("nickname") ?? "";
+color.value = localStorage.getItem("color") ?? color.value;
+function join() {
+  localStorage.setItem("nickname", nickname.value);
+  localStorage.setItem("color", color.value);
+  socket.send(JSON.stringify({
+    type: "Join",
+    name: nickname.value,
+    color: parseInt(color.value.slice(1), 16),
+  }));
+}
+nickname.addEventListener("change", () => { if (connected) { join(); } });
+color.addEventListener("change", () => { if (connected) { join(); } });
+socket.onopen = () => {
+  connected = true;
+  // Reclaim our mouse if we dropped out recently
+  let token = sessionStorage.getItem("token");
+  if (token) { socket.send(JSON.stringify({ type: "Resume", token })); }
+  join();
+};
+// Reloading reconnects and resumes our session, unless reloading won't help
+socket.onclose = () => {
+  connected = false;
+  if (!rejected) { setTimeout(() => location.reload(), 2000); }
+};
 
 canvas.addEventListener("click", async () => { await canvas.requestPointerLock(); });
 const sensitivity = document.getElementById("sensitivity");
 canvas.addEventListener("mousemove", (e) => {
   // Only send mouse movement when locked
-  if (document.pointerLockElement === canvas) {
+  if (document.pointerLockElement === canvas && !spectating) {
     socket.send(new Int32Array( [
       e.movementX * sensitivity.value,
       e.movementY * sensitivity.value
@@ -30,21 +74,88 @@ canvas.addEventListener("mousemove", (e) => {
   }
 });
 
+function handle_message(message) {
+  switch (message.type) {
+    case "Hello":
+      server = message;
+      level.own = message.object_id;
+      break;
+    case "Welcome":
+      level.own = message.object_id;
+      break;
+    case "Rejected":
+      rejected = true;
+      show_chat({ name: "Server", color: null, text: message.reason });
+      break;
+    case "Session":
+      sessionStorage.setItem("token", message.token);
+      break;
+    case "Chat":
+      show_chat(message);
+      break;
+    case "Admin":
+      show_chat({ name: "Server", color: null, text: message.authenticated ? "Logged in as admin" : "Wrong secret" });
+      break;
+    case "Notice":
+      show_chat({ name: "Server", color: null, text: message.text });
+      break;
+    case "Role":
+      spectating = message.spectating;
+      if (spectating) { level.own = null; }
+      role.textContent = spectating ? "Play" : "Spectate";
+      if (!spectating) { join(); }
+      break;
+  }
+}
+
+const chat_log = document.getElementById("chat-log");
+const chat_input = document.getElementById("chat-input");
+chat_input.addEventListener("keydown", (e) => {
+  if (e.key !== "Enter" || chat_input.value.trim() === "") { return; }
+  // "/admin <secret>" logs in, any other "/command" is run by the server
+  let text = chat_input.value.trim();
+  if (text.startsWith("/admin ")) {
+    socket.send(JSON.stringify({ type: "Admin", secret: text.slice(7) }));
+  } else if (text.startsWith("/")) {
+    socket.send(JSON.stringify({ type: "Command", command: text.slice(1) }));
+  } else {
+    socket.send(JSON.stringify({ type: "Chat", text }));
+  }
+  chat_input.value = "";
+});
+function show_chat(message) {
+  let line = document.createElement("div");
+  let name = document.createElement("b");
+  name.textContent = message.name + ": ";
+  if (message.color !== null) { name.style.color = "#" + message.color.toString(16).padStart(6, "0"); }
+  line.append(name, message.text);
+  chat_log.append(line);
+  while (chat_log.children.length > 50) { chat_log.firstChild.remove(); }
+}
+
 socket.onmessage = (msg) => {
+  if (typeof msg.data === "string") { handle_message(JSON.parse(msg.data)); return; }
   msg.data.arrayBuffer().then(bytes => {
-    const data = new Int32Array(bytes);
-    let update_count = Math.abs(data[0]);
-    if (data[0] < 0) { level.clear(); }
-    let cur_idx = 1;
-    for (let update = 0; update < update_count; update += 1) {
-      let size = data[cur_idx];
-      cur_idx += 1;
-      level.handle_update(data.subarray(cur_idx, cur_idx + size));
-      cur_idx += size;
+    const reader = new Reader(bytes);
+    switch (reader.byte()) {
+      case 0: // State
+        handle_state(reader);
+        break;
+      case 1: // Channels
+        level.handle_channels(reader);
+        break;
     }
   })
 };
 
+function handle_state(reader) {
+  let count = reader.signed();
+  if (count < 0) { level.clear(); }
+  for (let update = 0; update < Math.abs(count); update += 1) {
+    level.handle_update(reader.varint(), reader);
+  }
+}
+
 // Add a camera scaling to go from real game size to display size
 function render() {
   canvas.width = window.innerWidth;
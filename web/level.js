@@ -3,34 +3,37 @@ import Entity from "./entity.js";
 const sensitivity = document.getElementById("sensitivity");
 
 export default class Level {
-  constructor() { this.entities = new Map(); }
+  constructor() {
+    this.entities = new Map();
+    this.players = 0;
+    this.collected = 0;
+    this.in_exit = 0;
+    this.collect_goal = 0; // 0 when the level doesn't ask for items
+    this.time_left = null; // Seconds, null without a time limit
+    this.channels = []; // [{ channel, held, required }]
+    this.own = null; // Id of our mouse, null while spectating
+  }
   
   clear() {
     this.entities = new Map();
   }
 
-  handle_update(data) {
-    let key = data[0];
-    let flags = data[1];
-    let idx = 2;
+  handle_update(key, reader) {
+    let flags = reader.byte();
     if ((flags & 0b1) != 0) { this.entities.delete(key); return; }
     let entity = this.entities.get(key) ?? new Entity();
     if ((flags & 0b10) != 0) {
-      entity.pos = new Vec2(data[idx], data[idx + 1]);
-      idx += 2;
+      entity.pos = new Vec2(reader.signed(), reader.signed());
     }
     if ((flags & 0b100) != 0) {
       entity.points = [];
-      let point_count = data[idx];
-      idx += 1;
+      let point_count = reader.varint();
       for (let point = 0; point < point_count; point += 1) {
-        entity.points.push(new Vec2(data[idx], data[idx + 1]));
-        idx += 2;
+        entity.points.push(new Vec2(reader.signed(), reader.signed()));
       }
     }
     if ((flags & 0b1000) != 0) {
-      entity.update_material(data[idx]);
-      idx += 1;
+      entity.update_material(reader.signed());
     }
     if ((flags & 0b10000) != 0) {
       entity.hidden = true;
@@ -38,13 +41,50 @@ export default class Level {
     if ((flags & 0b100000) != 0) {
       entity.hidden = false;
     }
+    if ((flags & 0b1000000) != 0) {
+      entity.name = reader.string(reader.varint());
+    }
+    if ((flags & 0b10000000) != 0) {
+      entity.color = "#" + reader.varint().toString(16).padStart(6, "0");
+    }
     this.entities.set(key, entity);
   }
 
+  handle_channels(reader) {
+    this.players = reader.varint();
+    this.collected = reader.varint();
+    this.in_exit = reader.varint();
+    this.channels = [];
+    let count = reader.byte();
+    for (let channel = 0; channel < count; channel += 1) {
+      this.channels.push({ channel: reader.byte(), held: reader.byte(), required: reader.byte() });
+    }
+    this.collect_goal = reader.varint();
+    let time_left = reader.varint();
+    this.time_left = time_left == 0 ? null : time_left - 1;
+  }
+
   render(ctx) { 
     [...this.entities.entries()]
       .sort((a, b) => a[1].priority - b[1].priority)
-      .forEach(entity => {entity[1].render(ctx)} );
+      .forEach(entity => {entity[1].render(ctx, entity[0] === this.own)} );
+    this.render_hud(ctx);
+  }
+
+  render_hud(ctx) {
+    ctx.fillStyle = "black";
+    ctx.font = "16px sans-serif";
+    let lines = [`Players: ${this.players}`];
+    if (this.collect_goal > 0) { lines.push(`Collected: ${this.collected}/${this.collect_goal}`); }
+    else if (this.collected > 0) { lines.push(`Collected: ${this.collected}`); }
+    if (this.time_left !== null) {
+      lines.push(`Time left: ${Math.floor(this.time_left / 60)}:${String(this.time_left % 60).padStart(2, "0")}`);
+    }
+    if (this.in_exit > 0) { lines.push(`At exit: ${this.in_exit}/${this.players}`); }
+    for (const { channel, held, required } of this.channels) {
+      lines.push(`Channel ${channel}: ${held}/${required}`);
+    }
+    lines.forEach((line, idx) => ctx.fillText(line, 10, 40 + idx * 20));
   }
 }
 
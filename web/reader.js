@@ -0,0 +1,38 @@
+// Reads the compact broadcast encoding, mirroring ByteReader in src/protocol.rs
+const decoder = new TextDecoder();
+
+export default class Reader {
+  constructor(bytes) {
+    this.bytes = new Uint8Array(bytes);
+    this.idx = 0;
+  }
+
+  byte() {
+    return this.bytes[this.idx++];
+  }
+
+  // 7 bits a byte, low bits first, the top bit set on every byte but the last
+  varint() {
+    let value = 0;
+    let scale = 1;
+    let byte;
+    do {
+      byte = this.byte();
+      value += (byte & 0x7f) * scale;
+      scale *= 128;
+    } while (byte & 0x80);
+    return value;
+  }
+
+  // Zigzagged, so 0, 1, 2, 3 are 0, -1, 1, -2
+  signed() {
+    let value = this.varint();
+    return value % 2 == 0 ? value / 2 : -(value + 1) / 2;
+  }
+
+  string(length) {
+    let text = decoder.decode(this.bytes.subarray(this.idx, this.idx + length));
+    this.idx += length;
+    return text;
+  }
+}